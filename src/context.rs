@@ -0,0 +1,128 @@
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveTime, TimeDelta};
+
+use macroquad::prelude::*;
+
+use crate::{
+    input::InputState,
+    render::Renderer,
+    scenario::Scenario,
+    system::{TextureStorage, BG_COLOR, FG_COLOR},
+};
+
+/// Everything the windows read from the running application, passed to them instead of
+/// living in globals
+pub struct AppContext {
+    pub renderer: Box<dyn Renderer>,
+    pub textures: TextureStorage,
+    /// Pointer position as of the last input event
+    pub input: InputState,
+    pub clock: Clock,
+    pub config: Scenario,
+    pub theme: Theme,
+}
+
+impl AppContext {
+    pub fn new(
+        renderer: Box<dyn Renderer>,
+        textures: TextureStorage,
+        clock: Clock,
+        config: Scenario,
+    ) -> Self {
+        AppContext {
+            renderer,
+            textures,
+            input: InputState::default(),
+            clock,
+            config,
+            theme: Theme::default(),
+        }
+    }
+}
+
+/// Time since the start, read once per frame so that every window sees the same time
+pub struct Clock {
+    /// `None` for a fake clock, which only moves when it is advanced
+    start: Option<Instant>,
+    /// Time of day at the start, midnight for a fake clock
+    start_time: NaiveTime,
+    now: Duration,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Clock {
+            start: Some(Instant::now()),
+            start_time: Local::now().time(),
+            now: Duration::ZERO,
+        }
+    }
+
+    pub fn fake() -> Self {
+        Clock {
+            start: None,
+            start_time: NaiveTime::MIN,
+            now: Duration::ZERO,
+        }
+    }
+
+    /// Has to be called at the start of every frame
+    pub fn tick(&mut self) {
+        if let Some(start) = self.start {
+            self.now = start.elapsed();
+        }
+    }
+
+    /// Moves a fake clock forward, a real one ignores it
+    pub fn advance(&mut self, duration: Duration) {
+        if self.start.is_none() {
+            self.now += duration;
+        }
+    }
+
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// For the clock in the top bar
+    pub fn time_of_day(&self) -> NaiveTime {
+        self.start_time + TimeDelta::from_std(self.now).unwrap_or_default()
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.now.as_secs_f64()
+    }
+
+    /// Blinks twice a second, for text cursors
+    pub fn cursor_visible(&self) -> bool {
+        self.seconds().fract() < 0.5
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Colors of the windows and the bars
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub background: Color,
+    pub foreground: Color,
+    /// Top bar of the windows in the background
+    pub unfocused_header: Color,
+    pub unfocused_text: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: BG_COLOR,
+            foreground: FG_COLOR,
+            unfocused_header: Color::new(0.85, 0.85, 0.85, 1.0),
+            unfocused_text: Color::new(0.4, 0.4, 0.4, 1.0),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::window_manager::WindowId;
+use crate::windows::WindowKind;
+
+/// How many events are kept for `GET /events`
+const MAX_EVENTS: usize = 500;
+
+/// Commands the game master can send to the terminal
+pub enum ControlCommand {
+    Unlock,
+    Reset,
+    PopUp(String),
+    OpenDocument(String),
+    /// Name of an encrypted document
+    Decrypt(String),
+    /// Id of a redaction
+    Reveal(String),
+    RestartMiniGame,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct StatusReport {
+    pub hack_status: String,
+    pub is_unlocked: bool,
+    pub windows: Vec<WindowReport>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct WindowReport {
+    pub id: WindowId,
+    pub kind: WindowKind,
+    pub title: String,
+    pub top_left: (f32, f32),
+    pub size: (f32, f32),
+    pub is_visible: bool,
+    pub is_focused: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct EventRecord {
+    id: u64,
+    /// Seconds since UNIX epoch
+    time: u64,
+    message: String,
+}
+
+#[derive(Default)]
+struct SharedState {
+    status: StatusReport,
+    events: Vec<EventRecord>,
+    next_event_id: u64,
+}
+
+/// Embedded HTTP server that lets the game master watch and control the room.
+/// Requests are handled on a background thread, commands are picked up by `EscOS::tick`.
+pub struct ControlServer {
+    state: Arc<Mutex<SharedState>>,
+    commands: Receiver<ControlCommand>,
+}
+
+impl ControlServer {
+    pub fn start(bind: &str, token: Option<String>) -> Result<Self, String> {
+        let server = Server::http(bind).map_err(|err| err.to_string())?;
+        let state = Arc::new(Mutex::new(SharedState::default()));
+        let (sender, commands) = mpsc::channel();
+
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                handle_request(request, &thread_state, &sender, token.as_deref());
+            }
+        });
+
+        println!("Control server listening on {bind}");
+        Ok(ControlServer { state, commands })
+    }
+
+    pub fn poll_commands(&self) -> Vec<ControlCommand> {
+        self.commands.try_iter().collect()
+    }
+
+    pub fn publish_status(&self, status: StatusReport) {
+        self.state.lock().unwrap().status = status;
+    }
+
+    pub fn push_event(&self, message: String) {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_event_id;
+        state.next_event_id += 1;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|dur| dur.as_secs())
+            .unwrap_or_default();
+        state.events.push(EventRecord { id, time, message });
+
+        if state.events.len() > MAX_EVENTS {
+            let overflow = state.events.len() - MAX_EVENTS;
+            state.events.drain(..overflow);
+        }
+    }
+}
+
+fn handle_request(
+    mut request: Request,
+    state: &Mutex<SharedState>,
+    sender: &Sender<ControlCommand>,
+    token: Option<&str>,
+) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((url.as_str(), ""));
+
+    if let Some(token) = token {
+        let header_token = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("X-Control-Token"))
+            .map(|header| header.value.as_str().to_string());
+        let query_token = query_param(query, "token");
+        if header_token.as_deref() != Some(token) && query_token != Some(token) {
+            respond(request, 401, error_json("Missing or invalid token"));
+            return;
+        }
+    }
+
+    let mut body = String::new();
+    if request.as_reader().read_to_string(&mut body).is_err() {
+        respond(request, 400, error_json("Body is not valid UTF-8"));
+        return;
+    }
+    let body = body.trim().to_string();
+
+    let command = match (request.method(), path) {
+        (Method::Get, "/status") => {
+            let status = state.lock().unwrap().status.clone();
+            respond(request, 200, serde_json::to_string(&status).unwrap());
+            return;
+        }
+        (Method::Get, "/events") => {
+            let since = query_param(query, "since")
+                .and_then(|since| since.parse::<u64>().ok())
+                .unwrap_or(0);
+            let events = state
+                .lock()
+                .unwrap()
+                .events
+                .iter()
+                .filter(|event| event.id >= since)
+                .cloned()
+                .collect::<Vec<_>>();
+            respond(request, 200, serde_json::to_string(&events).unwrap());
+            return;
+        }
+        (Method::Post, "/unlock") => ControlCommand::Unlock,
+        (Method::Post, "/reset") => ControlCommand::Reset,
+        (Method::Post, "/minigame/restart") => ControlCommand::RestartMiniGame,
+        (Method::Post, "/popup") if !body.is_empty() => ControlCommand::PopUp(body),
+        (Method::Post, "/document") if !body.is_empty() => ControlCommand::OpenDocument(body),
+        (Method::Post, "/decrypt") if !body.is_empty() => ControlCommand::Decrypt(body),
+        (Method::Post, "/reveal") if !body.is_empty() => ControlCommand::Reveal(body),
+        (Method::Post, "/popup" | "/document" | "/decrypt" | "/reveal") => {
+            respond(request, 400, error_json("Request body must not be empty"));
+            return;
+        }
+        _ => {
+            respond(request, 404, error_json("Unknown endpoint"));
+            return;
+        }
+    };
+
+    if sender.send(command).is_ok() {
+        respond(request, 202, r#"{"queued":true}"#.to_string());
+    } else {
+        respond(request, 503, error_json("Terminal is shutting down"));
+    }
+}
+
+fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
+
+fn error_json(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+fn respond(request: Request, status: u16, json: String) {
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(json)
+        .with_status_code(status)
+        .with_header(header);
+    // The client may already be gone, nothing to do about that
+    let _ = request.respond(response);
+}
@@ -1,15 +1,46 @@
+use std::{collections::HashMap, mem, rc::Rc, time::Duration};
+
 use macroquad::prelude::*;
 
 use crate::{
-    system::{texture_storage, BG_COLOR, FG_COLOR, LAST_MOUSE_POS},
-    windows::{
-        draw_outlined_box, draw_window_top_bar, minimize_button, InputEvent, Window,
-        WindowReturnAction, HEADER_HEIGHT,
-    },
+    context::AppContext,
+    document_meta::{DocumentLink, Redaction},
+    input::InputEvent,
+    render::Renderer,
+    session::{DocumentView, WindowSnapshot, WindowState},
+    system::Page,
+    text_document,
+    vfs::VirtualFs,
+    window_chrome::{self, ChromeAction, WindowChrome},
+    windows::{Window, WindowKind, WindowReturnAction, HEADER_HEIGHT},
 };
 
+const MAX_DOC_WIDTH: f32 = 1200.0;
 const MAX_DOC_HEIGHT: f32 = 1000.0;
+/// Room for the header buttons
+const MIN_SIZE: Vec2 = vec2(660.0, 300.0);
 const SCROLL_SPEED: f32 = 35.0;
+/// Buttons in the top bar
+const HEADER_BUTTON_SIZE: f32 = 40.0;
+const HEADER_BUTTON_SPACING: f32 = 8.0;
+const HEADER_BUTTON_FONT_SIZE: u16 = 24;
+const ZOOM_INDICATOR_WIDTH: f32 = 70.0;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+/// Zoom factor of one wheel step or button press
+const ZOOM_STEP: f32 = 1.25;
+/// Encrypted pages are drawn in shuffled strips of this many texture pixels
+const SCRAMBLE_STRIP_HEIGHT: f32 = 6.0;
+const KEY_PROMPT_SIZE: Vec2 = vec2(520.0, 170.0);
+const KEY_PROMPT_FONT_SIZE: f32 = 30.0;
+const WRONG_KEY_DURATION: Duration = Duration::from_secs(2);
+const WRONG_KEY_COLOR: Color = RED;
+const REDACTION_COLOR: Color = BLACK;
+/// How long a revealed redaction takes to dissolve
+const DISSOLVE_SECONDS: f64 = 2.5;
+/// The bar falls apart in squares of this many screen pixels
+const DISSOLVE_CELL_SIZE: f32 = 8.0;
+const LINK_HOVER_COLOR: Color = Color::new(0.1, 0.3, 0.7, 0.2);
 
 pub struct VerticalScroller {
     pub height: f32,
@@ -22,8 +53,8 @@ impl VerticalScroller {
     pub const WIDTH: f32 = 10.0;
     const BG_COLOR: Color = Color::from_hex(0xA0A0A0);
 
-    pub fn draw(&self, top_left: Vec2) {
-        draw_rectangle(
+    pub fn draw(&self, context: &AppContext, top_left: Vec2) {
+        context.renderer.draw_rectangle(
             top_left.x,
             top_left.y,
             Self::WIDTH,
@@ -32,90 +63,205 @@ impl VerticalScroller {
         );
 
         let y = top_left.y + self.percent * (self.height - self.scroller_height);
-        draw_rectangle(top_left.x, y, Self::WIDTH, self.scroller_height, FG_COLOR);
+        context.renderer.draw_rectangle(
+            top_left.x,
+            y,
+            Self::WIDTH,
+            self.scroller_height,
+            context.theme.foreground,
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ZoomMode {
+    /// The page is as wide as the window
+    FitWidth,
+    /// The whole page is visible
+    FitWindow,
+    /// Zoomed by hand
+    Free,
+}
+
+#[derive(Clone, Copy)]
+enum HeaderButton {
+    Back,
+    Forward,
+    PreviousPage,
+    NextPage,
+    ZoomOut,
+    ZoomIn,
+    FitWidth,
+    FitWindow,
+}
+
+impl HeaderButton {
+    fn label(&self) -> &'static str {
+        match self {
+            HeaderButton::Back => "<-",
+            HeaderButton::Forward => "->",
+            HeaderButton::PreviousPage => "<",
+            HeaderButton::NextPage => ">",
+            HeaderButton::ZoomOut => "-",
+            HeaderButton::ZoomIn => "+",
+            HeaderButton::FitWidth => "W",
+            HeaderButton::FitWindow => "P",
+        }
     }
 }
 
+/// Document shown before or after following a link
+struct HistoryEntry {
+    document_name: String,
+    page: usize,
+    vertical_offset: f32,
+}
+
 pub struct DocumentWindow {
-    position: Vec2,
-    window_size: Vec2,
+    chrome: WindowChrome,
     document_name: String,
-    document_texture: Texture2D,
+    /// From the sidecar, shown in the top bar
+    title: String,
+    pages: Vec<Page>,
+    page: usize,
+    /// Scroll position of every page, restored when going back to it
+    page_offsets: Vec<f32>,
+    /// In texture pixels, like `horizontal_offset`
     vertical_offset: f32,
-    max_vertical_offset: f32,
+    horizontal_offset: f32,
+    /// Screen pixels per texture pixel
+    zoom: f32,
+    zoom_mode: ZoomMode,
+    /// Fingers that went down on the page, by touch id
+    touches: HashMap<u64, Vec2>,
+    /// Distance between the two fingers of a pinch at the last move
+    pinch_distance: Option<f32>,
     document_height: f32,
     scroller: VerticalScroller,
+    /// Page keys and typing go to the last clicked document
+    is_focused: bool,
+    /// Digits typed to jump to a page, `Enter` jumps
+    page_input: Option<String>,
+    /// Decides if the document is still encrypted
+    filesystem: Rc<VirtualFs>,
+    /// Key typed into the prompt of an encrypted document
+    key_input: String,
+    /// Clock time of the last wrong key
+    wrong_key_at: Option<Duration>,
+    /// From the sidecar
+    redactions: Vec<Redaction>,
+    /// When each revealed redaction started to dissolve, by id
+    revealed_at: HashMap<String, f64>,
+    /// From the sidecar or the text of the document
+    links: Vec<DocumentLink>,
+    back: Vec<HistoryEntry>,
+    forward: Vec<HistoryEntry>,
 
     is_visible: bool,
-    /// Relative to top-left
-    minimize_position_relative: Vec2,
-    minimize_size: Vec2,
 }
 
 impl Window for DocumentWindow {
-    async fn new_boxed() -> Box<dyn Window>
-    where
-        Self: Sized,
-    {
-        panic!("PLS don't call this function.")
-    }
-
     fn position(&self) -> Vec2 {
-        self.position
+        self.chrome.position()
     }
 
     fn top_left(&self) -> Vec2 {
-        self.position - self.window_size * 0.5
+        self.chrome.top_left()
     }
 
-    fn draw(&mut self) {
+    fn draw(&mut self, context: &AppContext) {
+        let theme = &context.theme;
         // Draw outer box
-        draw_outlined_box(
-            self.top_left().x,
-            self.top_left().y,
-            self.window_size.x,
-            self.window_size.y + 2.5,
+        let size = self.size();
+        context.renderer.draw_outlined_box(
+            Rect::new(self.top_left().x, self.top_left().y, size.x, size.y),
             5.0,
-            BG_COLOR,
-            FG_COLOR,
-        );
-        draw_window_top_bar(
-            &self.document_name,
-            30.0,
-            self.top_left().x,
-            self.top_left().y,
-            self.window_size.x,
-            HEADER_HEIGHT,
-            FG_COLOR,
-            BG_COLOR,
+            theme.background,
+            theme.foreground,
         );
+        self.chrome
+            .draw(context, &self.top_bar_text(), self.is_focused);
 
-        let params = DrawTextureParams {
-            source: Some(Rect {
-                x: 0.0,
-                y: self.vertical_offset,
-                w: self.document_texture.width(),
-                h: self.window_size.y - HEADER_HEIGHT,
-            }),
-            ..Default::default()
+        // Only the visible part of the page is drawn, scaled by the zoom
+        let page = self.current_page();
+        let view = self.view_rect();
+        let source = Rect {
+            x: self.horizontal_offset,
+            y: self.vertical_offset,
+            w: (view.w / self.zoom).min(page.size.x - self.horizontal_offset),
+            h: (view.h / self.zoom).min(page.size.y - self.vertical_offset),
         };
+        let page_position = self.page_position();
+        let is_encrypted = self.is_encrypted();
+        if let Some(texture) = &page.texture {
+            if is_encrypted {
+                self.draw_scrambled(context, texture, source, page_position);
+            } else {
+                context.renderer.draw_texture_ex(
+                    texture,
+                    page_position.x,
+                    page_position.y,
+                    WHITE,
+                    DrawTextureParams {
+                        source: Some(source),
+                        dest_size: Some(source.size() * self.zoom),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
 
-        draw_texture_ex(
-            &self.document_texture,
-            self.top_left().x + 2.5,
-            self.top_left().y + HEADER_HEIGHT,
-            WHITE,
-            params,
-        );
+        let scroller_pos = vec2(view.right() - VerticalScroller::WIDTH, view.y);
+        self.scroller.height = view.h;
+        self.scroller.percent = self.vertical_offset / self.max_vertical_offset().max(1.0);
+        self.scroller.draw(context, scroller_pos);
 
-        let scroller_pos = self.position
-            + vec2(
-                self.window_size.x * 0.5 - VerticalScroller::WIDTH - 2.5,
-                -self.window_size.y * 0.5 + HEADER_HEIGHT,
+        for (button, rect) in self.header_buttons() {
+            context
+                .renderer
+                .draw_outlined_box(rect, 3.0, theme.background, theme.foreground);
+            draw_centered_text(
+                context.renderer.as_ref(),
+                button.label(),
+                rect,
+                theme.foreground,
             );
-        self.scroller.draw(scroller_pos);
+        }
+        let indicator = self.zoom_indicator_rect();
+        context.renderer.draw_rectangle(
+            indicator.x,
+            indicator.y,
+            indicator.w,
+            indicator.h,
+            theme.background,
+        );
+        let zoom = format!("{:.0}%", self.zoom * 100.0);
+        draw_centered_text(
+            context.renderer.as_ref(),
+            &zoom,
+            indicator,
+            theme.foreground,
+        );
+
+        self.draw_redactions(context);
+        if !is_encrypted {
+            if let Some(link) = self.link_at(context.input.mouse_pos) {
+                let rect = self.page_rect_on_screen(link.rect);
+                if let Some(rect) = rect.intersect(view) {
+                    context.renderer.draw_rectangle(
+                        rect.x,
+                        rect.y,
+                        rect.w,
+                        rect.h,
+                        LINK_HOVER_COLOR,
+                    );
+                }
+            }
+        }
 
-        self.minimize_size = minimize_button(self.top_left() + self.minimize_position_relative);
+        if is_encrypted {
+            self.draw_key_prompt(context);
+        }
     }
 
     fn is_visible(&self) -> bool {
@@ -126,89 +272,729 @@ impl Window for DocumentWindow {
         self.is_visible = value;
     }
 
-    fn handle_input(&mut self, event: InputEvent) -> WindowReturnAction {
-        self.scroller.percent = self.vertical_offset / self.max_vertical_offset;
+    fn set_focused(&mut self, focused: bool) {
+        if !focused {
+            self.page_input = None;
+        }
+        self.is_focused = focused;
+    }
+
+    fn document_name(&self) -> Option<&str> {
+        Some(&self.document_name)
+    }
+
+    fn navigate_to(&mut self, context: &AppContext, document: String) {
+        self.follow_link(context, document);
+    }
+
+    fn update(&mut self, context: &AppContext) -> WindowReturnAction {
+        self.chrome.update(context);
+        // Relayouts after resizing as well
+        self.update_zoom();
+        WindowReturnAction::None
+    }
+
+    fn handle_input(&mut self, context: &AppContext, event: InputEvent) -> WindowReturnAction {
+        // The buttons of the document sit in the top bar as well
+        let on_header_button = match event {
+            InputEvent::MouseDown { pos, .. } | InputEvent::DoubleClick { pos } => self
+                .header_buttons()
+                .iter()
+                .any(|(_, rect)| rect.contains(pos)),
+            _ => false,
+        };
+        if !on_header_button {
+            match self.chrome.handle_input(context, &event) {
+                ChromeAction::None => {}
+                ChromeAction::Handled => return WindowReturnAction::None,
+                ChromeAction::Minimize => return WindowReturnAction::Minimize,
+                ChromeAction::Close => return WindowReturnAction::Close,
+            }
+        }
 
-        // Check if mouse is pressed in header part
         match event {
-            InputEvent::LeftMouse(pos, held) => {
-                if is_mouse_button_down(MouseButton::Left) && self.is_pos_in_header(pos) {
-                    let diff = unsafe { pos - LAST_MOUSE_POS };
-                    self.position += diff;
+            InputEvent::MouseDown {
+                button: MouseButton::Left,
+                pos,
+                modifiers,
+            } => {
+                let button = self
+                    .header_buttons()
+                    .into_iter()
+                    .find(|(_, rect)| rect.contains(pos));
+                let link = self
+                    .link_at(pos)
+                    .filter(|_| !self.is_encrypted())
+                    .map(|link| link.target.clone());
+                if let Some((button, _)) = button {
+                    self.press(context, button);
+                } else if let Some(target) = link {
+                    // Shift keeps this document open next to the linked one
+                    return WindowReturnAction::FollowLink(target, modifiers.shift);
                 }
-
-                if self.is_pos_in_minimize_button(pos) && !held {
-                    WindowReturnAction::Minimize
+            }
+            // Drag the page around
+            InputEvent::Drag {
+                button: MouseButton::Left,
+                pos,
+                delta,
+            } if self.pinch_distance.is_none() && self.view_rect().contains(pos) => {
+                self.horizontal_offset -= delta.x / self.zoom;
+                self.vertical_offset -= delta.y / self.zoom;
+                self.clamp_offsets();
+            }
+            InputEvent::Wheel {
+                pos,
+                delta,
+                modifiers,
+            } => {
+                if modifiers.ctrl {
+                    // Sideways scrolling does not zoom
+                    if delta.y == 0.0 {
+                        return WindowReturnAction::None;
+                    }
+                    self.zoom_at(ZOOM_STEP.powf(delta.y.signum()), pos);
+                } else if modifiers.shift {
+                    self.horizontal_offset -= delta.y * SCROLL_SPEED / self.zoom;
                 } else {
-                    WindowReturnAction::None
+                    self.horizontal_offset -= delta.x * SCROLL_SPEED / self.zoom;
+                    self.vertical_offset -= delta.y * SCROLL_SPEED / self.zoom;
                 }
+                self.clamp_offsets();
             }
-            InputEvent::Scroll(scroll) => {
-                self.vertical_offset = (self.vertical_offset - scroll * SCROLL_SPEED)
-                    .clamp(0.0, self.max_vertical_offset);
-                WindowReturnAction::None
+            InputEvent::TouchStart { id, pos } | InputEvent::TouchMove { id, pos, .. } => {
+                self.touches.insert(id, pos);
+                self.handle_pinch();
             }
-            _ => WindowReturnAction::None,
+            InputEvent::TouchEnd { id, .. } => {
+                self.touches.remove(&id);
+                self.handle_pinch();
+            }
+            _ if self.is_encrypted() => return self.handle_key_input(context, event),
+            _ if self.pages.len() > 1 => self.handle_keyboard(event),
+            _ => {}
         }
+
+        WindowReturnAction::None
     }
 
-    fn icon(&self) -> Option<Texture2D> {
-        texture_storage().document()
+    fn icon(&self, context: &AppContext) -> Option<Texture2D> {
+        context.textures.document()
     }
 
-    fn contains_pos(&self, pos: Vec2) -> bool {
-        let tl = self.top_left();
-        let br = self.top_left() + self.window_size;
-        let (x, y) = (pos.x, pos.y);
+    fn kind(&self) -> WindowKind {
+        WindowKind::Document
+    }
+
+    fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    fn snapshot(&self) -> Option<WindowSnapshot> {
+        Some(WindowSnapshot {
+            position: self.position().into(),
+            size: Some(self.size().into()),
+            is_visible: self.is_visible,
+            state: WindowState::Document {
+                name: self.document_name.clone(),
+                view: DocumentView {
+                    vertical_offset: self.vertical_offset,
+                    page: self.page,
+                    page_offsets: self.page_offsets.clone(),
+                },
+            },
+        })
+    }
 
-        x >= tl.x && x <= br.x && y >= tl.y && y <= br.y
+    fn contains_pos(&self, pos: Vec2) -> bool {
+        self.chrome.contains_pos(pos)
     }
 
     fn size(&self) -> Vec2 {
-        self.window_size
+        self.chrome.size()
     }
 }
 
 impl DocumentWindow {
-    pub fn new_boxed(document_name: String) -> Box<dyn Window> {
-        let document_texture = texture_storage().document_by_name(document_name.as_str());
-        let document_texture = if let Some(texture) = document_texture {
-            texture
-        } else {
-            texture_storage().fallback_document()
+    pub fn new_boxed(
+        context: &AppContext,
+        document_name: String,
+        filesystem: Rc<VirtualFs>,
+    ) -> Box<dyn Window> {
+        Box::new(Self::new(context, document_name, filesystem))
+    }
+
+    /// `position` is the center of the window
+    pub fn restore_boxed(
+        context: &AppContext,
+        document_name: String,
+        filesystem: Rc<VirtualFs>,
+        position: Vec2,
+        size: Option<Vec2>,
+        view: &DocumentView,
+    ) -> Box<dyn Window> {
+        let mut window = Self::new(context, document_name, filesystem);
+        if let Some(size) = size {
+            window.chrome.set_size(size);
+        }
+        window.chrome.set_position(position);
+        for (offset, saved) in window.page_offsets.iter_mut().zip(&view.page_offsets) {
+            *offset = *saved;
+        }
+        window.page = view.page.min(window.pages.len() - 1);
+        window.update_zoom();
+        window.vertical_offset = view
+            .vertical_offset
+            .clamp(0.0, window.max_vertical_offset());
+
+        Box::new(window)
+    }
+
+    fn new(context: &AppContext, document_name: String, filesystem: Rc<VirtualFs>) -> Self {
+        let textures = &context.textures;
+        let pages = match textures.document_pages(document_name.as_str()) {
+            Some(pages) => pages,
+            // The placeholder is rendered text, which needs a graphics context
+            None if !context.renderer.supports_textures() => {
+                vec![Page::blank(vec2(
+                    text_document::PAGE_WIDTH,
+                    text_document::MIN_PAGE_HEIGHT,
+                ))]
+            }
+            None => vec![Page::new(textures.fallback_document(&document_name))],
         };
 
-        let document_height = document_texture.height();
+        // Sized for the largest page, wide scans are zoomed out to fit
+        let document_height = pages.iter().map(|page| page.size.y).fold(0.0, f32::max);
         let height = document_height.min(MAX_DOC_HEIGHT);
-        let width = document_texture.width();
-        let max_vertical_offset = document_texture.height() - height + 2. * HEADER_HEIGHT;
+        let width = pages
+            .iter()
+            .map(|page| page.size.x)
+            .fold(0.0, f32::max)
+            .min(MAX_DOC_WIDTH);
 
-        let position = Vec2::new(200.0 + screen_width() * 0.5, 50.0 + height * 0.5);
+        let size = Vec2::new(width + 5.0, height - HEADER_HEIGHT + 5.0);
+        let position = Vec2::new(
+            200.0 + context.renderer.screen_size().x * 0.5,
+            50.0 + size.y * 0.5,
+        );
         let scroller = VerticalScroller {
-            height: height - 2.0 * HEADER_HEIGHT + 5.0,
+            height: size.y - HEADER_HEIGHT,
             scroller_height: 50.0,
             percent: 0.0,
         };
 
-        Box::new(DocumentWindow {
-            position,
-            window_size: Vec2::new(width + 5.0, height - HEADER_HEIGHT + 5.0),
+        // Redactions revealed before the document was opened are already gone
+        let meta = textures.document_meta(&document_name);
+        let redactions = meta.redactions;
+        let revealed_at = redactions
+            .iter()
+            .filter(|redaction| filesystem.is_revealed(redaction))
+            .map(|redaction| (redaction.id.clone(), f64::MIN))
+            .collect();
+
+        let mut window = DocumentWindow {
+            chrome: WindowChrome::new(position, size, MIN_SIZE),
+            title: textures.document_title(&document_name),
             document_name,
             document_height,
-            document_texture,
+            page: 0,
+            page_offsets: vec![0.0; pages.len()],
+            pages,
             vertical_offset: 0.0,
-            max_vertical_offset,
+            horizontal_offset: 0.0,
+            zoom: 1.0,
+            zoom_mode: ZoomMode::FitWidth,
+            touches: HashMap::new(),
+            pinch_distance: None,
             scroller,
+            is_focused: false,
+            page_input: None,
+            filesystem,
+            key_input: String::new(),
+            wrong_key_at: None,
+            redactions,
+            revealed_at,
+            links: meta.links,
+            back: vec![],
+            forward: vec![],
 
             is_visible: true,
-            minimize_position_relative: Vec2::new(width - 50.0, HEADER_HEIGHT * 0.5),
-            minimize_size: Vec2::ZERO,
-        })
+        };
+        window.update_zoom();
+
+        window
     }
 
-    fn is_pos_in_minimize_button(&self, pos: Vec2) -> bool {
-        let min_tl = self.top_left() + self.minimize_position_relative - self.minimize_size * 0.5;
-        let min_br = self.top_left() + self.minimize_position_relative + self.minimize_size * 0.5;
-        pos.x > min_tl.x && pos.x < min_br.x && pos.y > min_tl.y && pos.y < min_br.y
+    fn current_page(&self) -> Page {
+        self.pages[self.page].clone()
     }
+
+    /// Bars over the page
+    fn draw_redactions(&mut self, context: &AppContext) {
+        let now = context.clock.seconds();
+        for redaction in &self.redactions {
+            if redaction.page != self.page + 1 {
+                continue;
+            }
+            // Revealed while the document was open
+            if self.filesystem.is_revealed(redaction) {
+                self.revealed_at.entry(redaction.id.clone()).or_insert(now);
+            }
+            let progress = match self.revealed_at.get(&redaction.id) {
+                Some(at) => ((now - at) / DISSOLVE_SECONDS) as f32,
+                None => 0.0,
+            };
+            if progress >= 1.0 {
+                continue;
+            }
+
+            let bar = self.page_rect_on_screen(redaction.rect);
+            let (tl, size) = (bar.point(), bar.size());
+            let view = self.view_rect();
+            if progress <= 0.0 {
+                if let Some(rect) = bar.intersect(view) {
+                    context.renderer.draw_rectangle(
+                        rect.x,
+                        rect.y,
+                        rect.w,
+                        rect.h,
+                        REDACTION_COLOR,
+                    );
+                }
+                continue;
+            }
+
+            // Every cell disappears at its own time
+            let columns = (size.x / DISSOLVE_CELL_SIZE).ceil() as usize;
+            let rows = (size.y / DISSOLVE_CELL_SIZE).ceil() as usize;
+            for row in 0..rows {
+                for column in 0..columns {
+                    if cell_noise(row, column) < progress {
+                        continue;
+                    }
+                    let cell = Rect::new(
+                        tl.x + column as f32 * DISSOLVE_CELL_SIZE,
+                        tl.y + row as f32 * DISSOLVE_CELL_SIZE,
+                        DISSOLVE_CELL_SIZE.min(size.x - column as f32 * DISSOLVE_CELL_SIZE),
+                        DISSOLVE_CELL_SIZE.min(size.y - row as f32 * DISSOLVE_CELL_SIZE),
+                    );
+                    if let Some(rect) = cell.intersect(view) {
+                        context.renderer.draw_rectangle(
+                            rect.x,
+                            rect.y,
+                            rect.w,
+                            rect.h,
+                            REDACTION_COLOR,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.filesystem.is_encrypted(&self.document_name)
+    }
+
+    /// Draws the rows of the page in a fixed shuffled order, so the layout shows but the
+    /// text cannot be read
+    fn draw_scrambled(
+        &self,
+        context: &AppContext,
+        texture: &Texture2D,
+        source: Rect,
+        position: Vec2,
+    ) {
+        let strips = ((texture.height() / SCRAMBLE_STRIP_HEIGHT) as usize).max(1);
+        let mut y = source.y;
+        while y < source.bottom() {
+            let strip = (y / SCRAMBLE_STRIP_HEIGHT) as usize;
+            let strip_top = strip as f32 * SCRAMBLE_STRIP_HEIGHT;
+            let height = (strip_top + SCRAMBLE_STRIP_HEIGHT).min(source.bottom()) - y;
+            let from = (strip * 7919 + 13) % strips;
+            let strip_source = Rect::new(
+                source.x,
+                from as f32 * SCRAMBLE_STRIP_HEIGHT + (y - strip_top),
+                source.w,
+                height,
+            );
+            context.renderer.draw_texture_ex(
+                texture,
+                position.x,
+                position.y + (y - source.y) * self.zoom,
+                WHITE,
+                DrawTextureParams {
+                    source: Some(strip_source),
+                    dest_size: Some(strip_source.size() * self.zoom),
+                    ..Default::default()
+                },
+            );
+            y += height;
+        }
+    }
+
+    fn draw_key_prompt(&self, context: &AppContext) {
+        let theme = &context.theme;
+        let center = self.view_rect().center();
+        let tl = center - KEY_PROMPT_SIZE * 0.5;
+        context.renderer.draw_outlined_box(
+            Rect::new(tl.x, tl.y, KEY_PROMPT_SIZE.x, KEY_PROMPT_SIZE.y),
+            5.0,
+            theme.background,
+            theme.foreground,
+        );
+
+        let cursor = if self.is_focused && context.clock.cursor_visible() {
+            "_"
+        } else {
+            ""
+        };
+        let is_wrong = self
+            .wrong_key_at
+            .is_some_and(|at| context.clock.now().saturating_sub(at) < WRONG_KEY_DURATION);
+        // Documents without a key wait for their payload
+        let has_key = self.filesystem.has_key(&self.document_name);
+        let (status, status_color) = if !has_key {
+            ("Waiting for the decryption key", theme.foreground)
+        } else if is_wrong {
+            ("Wrong key", WRONG_KEY_COLOR)
+        } else {
+            ("Type the key and press Enter:", theme.foreground)
+        };
+        let key = if has_key {
+            format!("{}{cursor}", "*".repeat(self.key_input.chars().count()))
+        } else {
+            String::new()
+        };
+
+        let mut y = tl.y + KEY_PROMPT_FONT_SIZE * 1.5;
+        let lines = [
+            ("ENCRYPTED DOCUMENT", theme.foreground),
+            (status, status_color),
+            (key.as_str(), theme.foreground),
+        ];
+        for (text, color) in lines {
+            let measure = context
+                .renderer
+                .measure_text(text, KEY_PROMPT_FONT_SIZE as u16, 1.0);
+            context.renderer.draw_text(
+                text,
+                center.x - measure.width * 0.5,
+                y,
+                KEY_PROMPT_FONT_SIZE,
+                color,
+            );
+            y += KEY_PROMPT_FONT_SIZE * 1.4;
+        }
+    }
+
+    /// Typing goes to the key prompt while the document is encrypted
+    fn handle_key_input(&mut self, context: &AppContext, event: InputEvent) -> WindowReturnAction {
+        match event {
+            InputEvent::Text(char) => self.key_input.push(char),
+            InputEvent::KeyDown {
+                key: KeyCode::Backspace,
+                ..
+            } => {
+                self.key_input.pop();
+            }
+            InputEvent::KeyDown {
+                key: KeyCode::Enter | KeyCode::KpEnter,
+                repeat: false,
+                ..
+            } if !self.key_input.is_empty() => {
+                let key = mem::take(&mut self.key_input);
+                let success = self.filesystem.decrypt_with_key(&self.document_name, &key);
+                if !success {
+                    self.wrong_key_at = Some(context.clock.now());
+                }
+                return WindowReturnAction::DecryptAttempt(self.document_name.clone(), success);
+            }
+            _ => {}
+        }
+        WindowReturnAction::None
+    }
+
+    /// Where the top-left of the visible part of the page is drawn, pages narrower than the
+    /// window are centered
+    fn page_position(&self) -> Vec2 {
+        let view = self.view_rect();
+        let margin = ((view.w - self.current_page().size.x * self.zoom) * 0.5).max(0.0);
+        vec2(view.x + margin, view.y)
+    }
+
+    /// `[x, y, width, height]` in pixels of the page to the screen
+    fn page_rect_on_screen(&self, [x, y, w, h]: [f32; 4]) -> Rect {
+        let offset = vec2(self.horizontal_offset, self.vertical_offset);
+        let tl = self.page_position() + (vec2(x, y) - offset) * self.zoom;
+        Rect::new(tl.x, tl.y, w * self.zoom, h * self.zoom)
+    }
+
+    /// Link on the current page under the screen position
+    fn link_at(&self, pos: Vec2) -> Option<&DocumentLink> {
+        if !self.view_rect().contains(pos) {
+            return None;
+        }
+        self.links
+            .iter()
+            .filter(|link| link.page == self.page + 1)
+            .find(|link| self.page_rect_on_screen(link.rect).contains(pos))
+    }
+
+    fn history_entry(&self) -> HistoryEntry {
+        HistoryEntry {
+            document_name: self.document_name.clone(),
+            page: self.page,
+            vertical_offset: self.vertical_offset,
+        }
+    }
+
+    fn follow_link(&mut self, context: &AppContext, target: String) {
+        let current = self.history_entry();
+        self.back.push(current);
+        self.forward.clear();
+        self.show(
+            context,
+            HistoryEntry {
+                document_name: target,
+                page: 0,
+                vertical_offset: 0.0,
+            },
+        );
+    }
+
+    /// Replaces the document in place, the window keeps its place and size
+    fn show(&mut self, context: &AppContext, entry: HistoryEntry) {
+        let mut window = Self::new(context, entry.document_name, self.filesystem.clone());
+        window.chrome = self.chrome.clone();
+        window.update_zoom();
+        window.back = mem::take(&mut self.back);
+        window.forward = mem::take(&mut self.forward);
+        window.is_focused = self.is_focused;
+        window.go_to_page(entry.page);
+        window.vertical_offset = entry
+            .vertical_offset
+            .clamp(0.0, window.max_vertical_offset());
+
+        *self = window;
+    }
+
+    /// Part of the window the page is drawn in
+    fn view_rect(&self) -> Rect {
+        let content = self.chrome.content_rect();
+        Rect::new(content.x + 2.5, content.y, content.w - 5.0, content.h - 2.5)
+    }
+
+    fn max_vertical_offset(&self) -> f32 {
+        (self.current_page().size.y - self.view_rect().h / self.zoom).max(0.0)
+    }
+
+    fn max_horizontal_offset(&self) -> f32 {
+        (self.current_page().size.x - self.view_rect().w / self.zoom).max(0.0)
+    }
+
+    fn clamp_offsets(&mut self) {
+        self.vertical_offset = self.vertical_offset.clamp(0.0, self.max_vertical_offset());
+        self.horizontal_offset = self
+            .horizontal_offset
+            .clamp(0.0, self.max_horizontal_offset());
+    }
+
+    /// Follows the window and the page size in the fit modes
+    fn update_zoom(&mut self) {
+        let size = self.current_page().size;
+        let view = self.view_rect();
+        match self.zoom_mode {
+            ZoomMode::FitWidth => self.zoom = view.w / size.x,
+            ZoomMode::FitWindow => self.zoom = (view.w / size.x).min(view.h / size.y),
+            ZoomMode::Free => {}
+        }
+        self.clamp_offsets();
+    }
+
+    /// Keeps the point of the page under `anchor` in place
+    fn zoom_at(&mut self, factor: f32, anchor: Vec2) {
+        let view = self.view_rect();
+        let anchor = anchor.clamp(view.point(), view.point() + view.size()) - view.point();
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let offset =
+            vec2(self.horizontal_offset, self.vertical_offset) + anchor / self.zoom - anchor / zoom;
+
+        self.zoom = zoom;
+        self.zoom_mode = ZoomMode::Free;
+        self.horizontal_offset = offset.x;
+        self.vertical_offset = offset.y;
+        self.clamp_offsets();
+    }
+
+    /// Two fingers on the window zoom around their center
+    fn handle_pinch(&mut self) {
+        let touches = self.touches.values().copied().collect::<Vec<_>>();
+        let [first, second] = touches.as_slice() else {
+            self.pinch_distance = None;
+            return;
+        };
+
+        let center = (*first + *second) * 0.5;
+        let distance = first.distance(*second);
+        if !self.view_rect().contains(center) {
+            self.pinch_distance = None;
+            return;
+        }
+        if let Some(previous) = self.pinch_distance.filter(|previous| *previous > 0.0) {
+            self.zoom_at(distance / previous, center);
+        }
+        self.pinch_distance = Some(distance);
+    }
+
+    fn press(&mut self, context: &AppContext, button: HeaderButton) {
+        let center = self.view_rect().center();
+        match button {
+            HeaderButton::Back => {
+                if let Some(entry) = self.back.pop() {
+                    let current = self.history_entry();
+                    self.forward.push(current);
+                    self.show(context, entry);
+                }
+            }
+            HeaderButton::Forward => {
+                if let Some(entry) = self.forward.pop() {
+                    let current = self.history_entry();
+                    self.back.push(current);
+                    self.show(context, entry);
+                }
+            }
+            HeaderButton::PreviousPage => self.go_to_page(self.page.saturating_sub(1)),
+            HeaderButton::NextPage => self.go_to_page(self.page + 1),
+            HeaderButton::ZoomOut => self.zoom_at(1.0 / ZOOM_STEP, center),
+            HeaderButton::ZoomIn => self.zoom_at(ZOOM_STEP, center),
+            HeaderButton::FitWidth => {
+                self.zoom_mode = ZoomMode::FitWidth;
+                self.update_zoom();
+            }
+            HeaderButton::FitWindow => {
+                self.zoom_mode = ZoomMode::FitWindow;
+                self.update_zoom();
+            }
+        }
+    }
+
+    /// Keeps the scroll position of the page that is left
+    fn go_to_page(&mut self, page: usize) {
+        let page = page.min(self.pages.len() - 1);
+        self.page_offsets[self.page] = self.vertical_offset;
+        self.page = page;
+        self.update_zoom();
+        self.vertical_offset = self.page_offsets[page].clamp(0.0, self.max_vertical_offset());
+    }
+
+    /// Page keys flip pages, typing a number and `Enter` jumps to that page
+    fn handle_keyboard(&mut self, event: InputEvent) {
+        let key = match event {
+            InputEvent::Text(char) if char.is_ascii_digit() => {
+                self.page_input.get_or_insert_with(String::new).push(char);
+                return;
+            }
+            InputEvent::KeyDown { key, .. } => key,
+            _ => return,
+        };
+
+        match key {
+            KeyCode::PageDown => self.go_to_page(self.page + 1),
+            KeyCode::PageUp => self.go_to_page(self.page.saturating_sub(1)),
+            KeyCode::Backspace => {
+                if let Some(input) = self.page_input.as_mut() {
+                    input.pop();
+                }
+            }
+            KeyCode::Escape => self.page_input = None,
+            KeyCode::Enter | KeyCode::KpEnter => {
+                let page = self
+                    .page_input
+                    .take()
+                    .and_then(|input| input.parse::<usize>().ok());
+                if let Some(page) = page {
+                    self.go_to_page(page.saturating_sub(1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Title with the page indicator, or the page being typed
+    fn top_bar_text(&self) -> String {
+        match &self.page_input {
+            _ if self.pages.len() < 2 => self.title.clone(),
+            Some(input) => format!("{}  Go to page: {input}_", self.title),
+            None => format!("{}  {}/{}", self.title, self.page + 1, self.pages.len()),
+        }
+    }
+
+    /// History and page buttons on the left, zoom buttons on the right of the top bar
+    fn header_buttons(&self) -> Vec<(HeaderButton, Rect)> {
+        let tl = self.top_left();
+        let y = tl.y + (HEADER_HEIGHT - HEADER_BUTTON_SIZE) * 0.5;
+        let button = |x: f32| Rect::new(x, y, HEADER_BUTTON_SIZE, HEADER_BUTTON_SIZE);
+        let step = HEADER_BUTTON_SIZE + HEADER_BUTTON_SPACING;
+
+        let mut buttons = vec![];
+        let mut x = tl.x + HEADER_BUTTON_SPACING;
+        if !self.back.is_empty() || !self.forward.is_empty() {
+            buttons.push((HeaderButton::Back, button(x)));
+            buttons.push((HeaderButton::Forward, button(x + step)));
+            x += 2.0 * step;
+        }
+        if self.pages.len() > 1 {
+            buttons.push((HeaderButton::PreviousPage, button(x)));
+            buttons.push((HeaderButton::NextPage, button(x + step)));
+        }
+
+        // From right to left, the zoom indicator sits between `-` and `+`
+        let mut x = tl.x + self.size().x - window_chrome::BUTTONS_WIDTH - HEADER_BUTTON_SIZE;
+        for zoom_button in [
+            HeaderButton::FitWindow,
+            HeaderButton::FitWidth,
+            HeaderButton::ZoomIn,
+        ] {
+            buttons.push((zoom_button, button(x)));
+            x -= step;
+        }
+        x -= ZOOM_INDICATOR_WIDTH + HEADER_BUTTON_SPACING;
+        buttons.push((HeaderButton::ZoomOut, button(x)));
+
+        buttons
+    }
+
+    fn zoom_indicator_rect(&self) -> Rect {
+        let buttons = self.header_buttons();
+        let zoom_out = buttons.last().expect("Zoom buttons are always shown.").1;
+        Rect::new(
+            zoom_out.right() + HEADER_BUTTON_SPACING,
+            zoom_out.y,
+            ZOOM_INDICATOR_WIDTH,
+            zoom_out.h,
+        )
+    }
+}
+
+/// Fixed pseudo-random value from 0 to 1 for a cell of a dissolving redaction
+fn cell_noise(row: usize, column: usize) -> f32 {
+    let mut hash =
+        (row as u32).wrapping_mul(0x9E37_79B1) ^ (column as u32).wrapping_mul(0x85EB_CA77);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2C1B_3C6D);
+    hash ^= hash >> 12;
+    (hash % 1000) as f32 / 1000.0
+}
+
+fn draw_centered_text(renderer: &dyn Renderer, text: &str, rect: Rect, color: Color) {
+    let measure = renderer.measure_text(text, HEADER_BUTTON_FONT_SIZE, 1.0);
+    renderer.draw_text(
+        text,
+        rect.center().x - measure.width * 0.5,
+        rect.center().y + measure.height * 0.5,
+        HEADER_BUTTON_FONT_SIZE as f32,
+        color,
+    );
 }
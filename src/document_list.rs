@@ -1,78 +1,162 @@
+use std::rc::Rc;
+
 use macroquad::{
     color::{Color, WHITE},
-    input::{is_mouse_button_down, MouseButton},
-    math::{vec2, Vec2},
-    texture::{draw_texture, get_screen_data, Texture2D},
-    window::{screen_height, screen_width},
+    input::{KeyCode, MouseButton},
+    math::{vec2, Rect, Vec2},
+    texture::{DrawTextureParams, Texture2D},
 };
 
 use crate::{
-    system::{texture_storage, BG_COLOR, FG_COLOR, LAST_MOUSE_POS},
-    windows::{
-        draw_outlined_box, draw_window_top_bar, minimize_button, InputEvent, Window,
-        WindowReturnAction, HEADER_HEIGHT,
-    },
+    context::AppContext,
+    document_meta::DocumentMeta,
+    input::InputEvent,
+    popup::PopUp,
+    render::Renderer,
+    session::{WindowSnapshot, WindowState},
+    vfs::{VirtualFile, VirtualFs},
+    window_chrome::{ChromeAction, WindowChrome},
+    windows::{Window, WindowKind, WindowReturnAction, HEADER_HEIGHT},
 };
 
 const WIDTH: f32 = 1000.0;
-const HEIGHT: f32 = 500.0;
+const HEIGHT: f32 = 700.0;
+/// Room for the search box and the sort button
+const MIN_SIZE: Vec2 = Vec2::new(600.0, 450.0);
+const PADDING: f32 = 20.0;
+const SEARCH_HEIGHT: f32 = 50.0;
+const SORT_BUTTON_WIDTH: f32 = 260.0;
+const THUMBNAIL_SIZE: Vec2 = Vec2::new(140.0, 180.0);
+const CELL_SIZE: Vec2 = Vec2::new(180.0, 240.0);
+const FONT_SIZE: f32 = 22.0;
+const LOCKED_COLOR: Color = Color::new(1.0, 1.0, 1.0, 0.7);
+const LOCKED_TEXT: &str = "Document is locked.";
 
-pub struct DocumentList {
-    position: Vec2,
-    window_size: Vec2,
-    is_visible: bool,
-    /// Relative to top-left
-    minimize_position_relative: Vec2,
-    minimize_size: Vec2,
+#[derive(Clone, Copy)]
+enum SortOrder {
+    Title,
+    /// Newest first
+    Date,
+    /// Most secret first
+    Classification,
 }
 
-impl Window for DocumentList {
-    async fn new_boxed() -> Box<dyn Window>
-    where
-        Self: Sized,
-    {
-        let list = DocumentList {
-            position: vec2(screen_width() * 0.5, screen_height() * 0.4),
-            window_size: vec2(1000.0, 500.0),
-            is_visible: true,
-            minimize_position_relative: vec2(WIDTH - 50.0, HEADER_HEIGHT * 0.5),
-            minimize_size: Vec2::ZERO,
-        };
+impl SortOrder {
+    fn next(self) -> Self {
+        match self {
+            SortOrder::Title => SortOrder::Date,
+            SortOrder::Date => SortOrder::Classification,
+            SortOrder::Classification => SortOrder::Title,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortOrder::Title => "Sort: title",
+            SortOrder::Date => "Sort: date",
+            SortOrder::Classification => "Sort: class",
+        }
+    }
+}
+
+/// A document with its sidecar, as shown in the grid
+struct Entry<'a> {
+    file: &'a VirtualFile,
+    title: String,
+    meta: DocumentMeta,
+}
 
-        Box::new(list)
+impl Entry<'_> {
+    /// Name of the document texture
+    fn name(&self) -> &str {
+        self.file.document.as_deref().unwrap_or_default()
     }
+}
 
+pub struct DocumentList {
+    chrome: WindowChrome,
+    filesystem: Rc<VirtualFs>,
+    query: String,
+    sort_order: SortOrder,
+    /// Rows scrolled down
+    first_row: usize,
+    /// Area and document path of every entry drawn in the last frame
+    entry_rects: Vec<(Rect, String)>,
+    is_visible: bool,
+    is_focused: bool,
+}
+
+impl Window for DocumentList {
     fn position(&self) -> Vec2 {
-        self.position
+        self.chrome.position()
     }
 
     fn top_left(&self) -> Vec2 {
-        self.position - self.window_size * 0.5
+        self.chrome.top_left()
     }
 
-    fn draw(&mut self) {
+    fn draw(&mut self, context: &AppContext) {
+        let theme = &context.theme;
         // Draw outer box
-        draw_outlined_box(
-            self.top_left().x,
-            self.top_left().y,
-            self.window_size.x,
-            self.window_size.y + 2.5,
+        let size = self.size();
+        context.renderer.draw_outlined_box(
+            Rect::new(self.top_left().x, self.top_left().y, size.x, size.y),
             5.0,
-            BG_COLOR,
-            FG_COLOR,
-        );
-        draw_window_top_bar(
-            "Document Search",
-            30.0,
-            self.top_left().x,
-            self.top_left().y,
-            self.window_size.x,
-            HEADER_HEIGHT,
-            FG_COLOR,
-            BG_COLOR,
+            theme.background,
+            theme.foreground,
         );
+        self.chrome.draw(context, &self.title(), self.is_focused);
 
-        self.minimize_size = minimize_button(self.top_left() + self.minimize_position_relative);
+        self.draw_search_box(context);
+
+        // Grid of thumbnails, locked documents are listed but cannot be opened yet
+        let columns = (((size.x - 2.0 * PADDING) / CELL_SIZE.x) as usize).max(1);
+        let grid_top = self.top_left().y + HEADER_HEIGHT + SEARCH_HEIGHT + 2.0 * PADDING;
+        let rows = ((self.top_left().y + size.y - grid_top) / CELL_SIZE.y) as usize;
+        let filesystem = self.filesystem.clone();
+        let documents = self.entries(context, &filesystem);
+        let total_rows = documents.len().div_ceil(columns);
+        self.first_row = self.first_row.min(total_rows.saturating_sub(rows));
+
+        self.entry_rects.clear();
+        for (index, entry) in documents
+            .iter()
+            .enumerate()
+            .skip(self.first_row * columns)
+            .take(rows * columns)
+        {
+            let row = index / columns - self.first_row;
+            let column = index % columns;
+            let cell = vec2(
+                self.top_left().x + PADDING + column as f32 * CELL_SIZE.x,
+                grid_top + row as f32 * CELL_SIZE.y,
+            );
+            let overlay = if filesystem
+                .locked(&entry.file.path, context.clock.now())
+                .is_some()
+            {
+                Some("LOCKED")
+            } else if filesystem.is_encrypted(entry.name()) {
+                Some("ENCRYPTED")
+            } else {
+                None
+            };
+            self.draw_entry(context, entry, cell, overlay);
+            self.entry_rects.push((
+                Rect::new(cell.x, cell.y, CELL_SIZE.x, CELL_SIZE.y),
+                entry.file.path.clone(),
+            ));
+        }
+
+        if documents.is_empty() {
+            context.renderer.draw_text(
+                "No documents found",
+                self.top_left().x + PADDING,
+                grid_top + FONT_SIZE,
+                FONT_SIZE * 1.5,
+                theme.foreground,
+            );
+        }
     }
 
     fn is_visible(&self) -> bool {
@@ -83,46 +167,335 @@ impl Window for DocumentList {
         self.is_visible = value;
     }
 
+    fn set_focused(&mut self, focused: bool) {
+        self.is_focused = focused;
+    }
+
     fn size(&self) -> Vec2 {
-        self.window_size
+        self.chrome.size()
+    }
+
+    fn update(&mut self, context: &AppContext) -> WindowReturnAction {
+        self.chrome.update(context);
+        WindowReturnAction::None
     }
 
-    fn handle_input(&mut self, event: InputEvent) -> WindowReturnAction {
-        // Check if mouse is pressed in header part
+    fn handle_input(&mut self, context: &AppContext, event: InputEvent) -> WindowReturnAction {
+        match self.chrome.handle_input(context, &event) {
+            ChromeAction::None => {}
+            ChromeAction::Handled => return WindowReturnAction::None,
+            ChromeAction::Minimize => return WindowReturnAction::Minimize,
+            ChromeAction::Close => return WindowReturnAction::Close,
+        }
+
         match event {
-            InputEvent::LeftMouse(pos, held) => {
-                if is_mouse_button_down(MouseButton::Left) && self.is_pos_in_header(pos) {
-                    let diff = unsafe { pos - LAST_MOUSE_POS };
-                    self.position += diff;
+            InputEvent::MouseDown {
+                button: MouseButton::Left,
+                pos,
+                ..
+            } => {
+                if self.sort_button_rect().contains(pos) {
+                    self.sort_order = self.sort_order.next();
+                    WindowReturnAction::None
+                } else {
+                    self.open_entry_at(context, pos)
                 }
-
-                if self.is_pos_in_minimize_button(pos) && !held {
-                    WindowReturnAction::Minimize
+            }
+            InputEvent::Wheel { delta, .. } if delta.y != 0.0 => {
+                self.first_row = if delta.y > 0.0 {
+                    self.first_row.saturating_sub(1)
                 } else {
-                    WindowReturnAction::None
+                    self.first_row + 1
+                };
+                WindowReturnAction::None
+            }
+            // Typing goes into the search box
+            InputEvent::Text(char) => {
+                self.query.push(char);
+                self.first_row = 0;
+                WindowReturnAction::None
+            }
+            InputEvent::KeyDown { key, .. } => {
+                match key {
+                    KeyCode::Backspace => {
+                        self.query.pop();
+                    }
+                    KeyCode::Escape => self.query.clear(),
+                    _ => {}
                 }
+                WindowReturnAction::None
             }
             _ => WindowReturnAction::None,
         }
     }
 
-    fn icon(&self) -> Option<Texture2D> {
-        texture_storage().document()
+    fn icon(&self, context: &AppContext) -> Option<Texture2D> {
+        context.textures.document()
+    }
+
+    fn kind(&self) -> WindowKind {
+        WindowKind::DocumentList
+    }
+
+    fn title(&self) -> String {
+        "Document Search".to_string()
+    }
+
+    fn snapshot(&self) -> Option<WindowSnapshot> {
+        Some(WindowSnapshot {
+            position: self.position().into(),
+            size: Some(self.size().into()),
+            is_visible: self.is_visible,
+            state: WindowState::DocumentList,
+        })
     }
 
     fn contains_pos(&self, pos: Vec2) -> bool {
-        let tl = self.top_left();
-        let br = self.top_left() + self.window_size;
-        let (x, y) = (pos.x, pos.y);
+        self.chrome.contains_pos(pos)
+    }
+}
 
-        x >= tl.x && x <= br.x && y >= tl.y && y <= br.y
+/// Cuts the text and adds `...` if it is wider than `max_width`
+fn shorten(renderer: &dyn Renderer, text: &str, max_width: f32) -> String {
+    let fits = |text: &str| renderer.measure_text(text, FONT_SIZE as u16, 1.0).width <= max_width;
+    if fits(text) {
+        return text.to_string();
     }
+
+    let mut chars = text.chars().collect::<Vec<_>>();
+    while chars.pop().is_some() {
+        let shortened = format!("{}...", chars.iter().collect::<String>());
+        if fits(&shortened) {
+            return shortened;
+        }
+    }
+
+    "...".to_string()
 }
 
 impl DocumentList {
-    fn is_pos_in_minimize_button(&self, pos: Vec2) -> bool {
-        let min_tl = self.top_left() + self.minimize_position_relative - self.minimize_size * 0.5;
-        let min_br = self.top_left() + self.minimize_position_relative + self.minimize_size * 0.5;
-        pos.x > min_tl.x && pos.x < min_br.x && pos.y > min_tl.y && pos.y < min_br.y
+    pub fn new_boxed(context: &AppContext, filesystem: Rc<VirtualFs>) -> Box<dyn Window> {
+        Self::new_boxed_at(context.renderer.screen_size() * 0.5, None, filesystem)
+    }
+
+    /// Centered on `position`, with the default size if `size` is `None`
+    pub fn new_boxed_at(
+        position: Vec2,
+        size: Option<Vec2>,
+        filesystem: Rc<VirtualFs>,
+    ) -> Box<dyn Window> {
+        let size = size.unwrap_or(vec2(WIDTH, HEIGHT));
+        Box::new(DocumentList {
+            // Opened once when the system is unlocked
+            chrome: WindowChrome::new(position, size, MIN_SIZE).without_close_button(),
+            filesystem,
+            query: String::new(),
+            sort_order: SortOrder::Title,
+            first_row: 0,
+            entry_rects: vec![],
+            is_visible: true,
+            is_focused: false,
+        })
+    }
+
+    /// Documents matching the query in the current order. The query is matched
+    /// case-insensitively against the path, owner and the sidecar. The text of an encrypted
+    /// document is only searched once it is decrypted, so it cannot give the document away.
+    fn entries<'a>(&self, context: &AppContext, filesystem: &'a VirtualFs) -> Vec<Entry<'a>> {
+        let query = self.query.trim().to_lowercase();
+        let mut entries = filesystem
+            .documents()
+            .into_iter()
+            .map(|file| {
+                let name = file.document.as_deref().unwrap_or_default();
+                let meta = context.textures.document_meta(name);
+                let title = meta.title.clone().unwrap_or(file.name().to_string());
+                Entry { file, title, meta }
+            })
+            .filter(|entry| {
+                query.is_empty()
+                    || [
+                        entry.title.as_str(),
+                        entry.file.path.as_str(),
+                        entry.file.owner.as_str(),
+                    ]
+                    .iter()
+                    .any(|field| field.to_lowercase().contains(&query))
+                    || entry
+                        .meta
+                        .matches(&query, !filesystem.is_encrypted(entry.name()))
+            })
+            .collect::<Vec<_>>();
+
+        entries.sort_by(|a, b| match self.sort_order {
+            SortOrder::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortOrder::Date => b.meta.date.cmp(&a.meta.date),
+            SortOrder::Classification => b.meta.classification.cmp(&a.meta.classification),
+        });
+        entries
+    }
+
+    fn sort_button_rect(&self) -> Rect {
+        let tl = self.top_left();
+        Rect::new(
+            tl.x + self.size().x - PADDING - SORT_BUTTON_WIDTH,
+            tl.y + HEADER_HEIGHT + PADDING,
+            SORT_BUTTON_WIDTH,
+            SEARCH_HEIGHT,
+        )
+    }
+
+    fn draw_search_box(&mut self, context: &AppContext) {
+        let theme = &context.theme;
+        let position = self.top_left() + vec2(PADDING, HEADER_HEIGHT + PADDING);
+        let size = vec2(
+            self.size().x - 3.0 * PADDING - SORT_BUTTON_WIDTH,
+            SEARCH_HEIGHT,
+        );
+        context.renderer.draw_outlined_box(
+            Rect::new(position.x, position.y, size.x, size.y),
+            5.0,
+            theme.background,
+            theme.foreground,
+        );
+        let cursor = if self.is_focused && context.clock.cursor_visible() {
+            "_"
+        } else {
+            ""
+        };
+        if self.query.is_empty() && cursor.is_empty() {
+            context.renderer.draw_text(
+                "Search...",
+                position.x + 15.0,
+                position.y + size.y * 0.65,
+                30.0,
+                Color::from_hex(0xA0A0A0),
+            );
+        } else {
+            // Long queries show their end
+            let mut text = format!("{}{cursor}", self.query);
+            while context.renderer.measure_text(&text, 30, 1.0).width > size.x - 30.0 {
+                text.remove(0);
+            }
+            context.renderer.draw_text(
+                &text,
+                position.x + 15.0,
+                position.y + size.y * 0.65,
+                30.0,
+                theme.foreground,
+            );
+        }
+
+        let sort = self.sort_button_rect();
+        context
+            .renderer
+            .draw_outlined_box(sort, 5.0, theme.foreground, theme.foreground);
+        let label = self.sort_order.label();
+        let dim = context.renderer.measure_text(label, 30, 1.0);
+        context.renderer.draw_text(
+            label,
+            sort.x + (sort.w - dim.width) * 0.5,
+            sort.y + sort.h * 0.65,
+            30.0,
+            theme.background,
+        );
+    }
+
+    /// `overlay` covers the thumbnail of documents that cannot be read yet
+    fn draw_entry(&self, context: &AppContext, entry: &Entry, cell: Vec2, overlay: Option<&str>) {
+        let theme = &context.theme;
+        let texture = context.textures.document_by_name(entry.name());
+        let thumbnail_left = cell.x + (CELL_SIZE.x - THUMBNAIL_SIZE.x) * 0.5;
+        context.renderer.draw_outlined_box(
+            Rect::new(thumbnail_left, cell.y, THUMBNAIL_SIZE.x, THUMBNAIL_SIZE.y),
+            2.0,
+            theme.background,
+            theme.foreground,
+        );
+
+        if let Some(texture) = texture {
+            // Keep the aspect ratio
+            let scale =
+                (THUMBNAIL_SIZE.x / texture.width()).min(THUMBNAIL_SIZE.y / texture.height());
+            let size = texture.size() * scale;
+            let offset = (THUMBNAIL_SIZE - size) * 0.5;
+            context.renderer.draw_texture_ex(
+                &texture,
+                thumbnail_left + offset.x,
+                cell.y + offset.y,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(size),
+                    ..Default::default()
+                },
+            );
+        }
+        if let Some(classification) = entry.meta.classification {
+            let label = classification.label();
+            let dim = context.renderer.measure_text(label, FONT_SIZE as u16, 0.8);
+            context.renderer.draw_rectangle(
+                thumbnail_left,
+                cell.y,
+                THUMBNAIL_SIZE.x,
+                FONT_SIZE,
+                classification.color(),
+            );
+            context.renderer.draw_text(
+                label,
+                thumbnail_left + (THUMBNAIL_SIZE.x - dim.width) * 0.5,
+                cell.y + FONT_SIZE * 0.75,
+                FONT_SIZE * 0.8,
+                theme.background,
+            );
+        }
+        if let Some(overlay) = overlay {
+            context.renderer.draw_outlined_box(
+                Rect::new(thumbnail_left, cell.y, THUMBNAIL_SIZE.x, THUMBNAIL_SIZE.y),
+                2.0,
+                LOCKED_COLOR,
+                theme.foreground,
+            );
+            let dim = context.renderer.measure_text(overlay, 1, FONT_SIZE * 1.3);
+            context.renderer.draw_text(
+                overlay,
+                thumbnail_left + (THUMBNAIL_SIZE.x - dim.width) * 0.5,
+                cell.y + THUMBNAIL_SIZE.y * 0.5,
+                FONT_SIZE * 1.3,
+                theme.foreground,
+            );
+        }
+
+        // Name below, shortened to fit the cell
+        let name = shorten(context.renderer.as_ref(), &entry.title, CELL_SIZE.x - 10.0);
+        let dim = context.renderer.measure_text(&name, FONT_SIZE as u16, 1.0);
+        context.renderer.draw_text(
+            &name,
+            cell.x + (CELL_SIZE.x - dim.width) * 0.5,
+            cell.y + THUMBNAIL_SIZE.y + FONT_SIZE,
+            FONT_SIZE,
+            theme.foreground,
+        );
+    }
+
+    fn open_entry_at(&self, context: &AppContext, pos: Vec2) -> WindowReturnAction {
+        let Some((_, path)) = self.entry_rects.iter().find(|(rect, _)| rect.contains(pos)) else {
+            return WindowReturnAction::None;
+        };
+        if self.filesystem.locked(path, context.clock.now()).is_some() {
+            return WindowReturnAction::NewWindow(Box::new(PopUp::new_with_text(
+                context,
+                LOCKED_TEXT.to_string(),
+            )));
+        }
+
+        match self
+            .filesystem
+            .get(path)
+            .and_then(|file| file.document.clone())
+        {
+            // The system reports documents that do not exist
+            Some(name) => WindowReturnAction::OpenDocument(name),
+            None => WindowReturnAction::None,
+        }
     }
 }
@@ -0,0 +1,117 @@
+use std::{fs, io, path::Path};
+
+use macroquad::color::{Color, DARKGREEN, MAROON, ORANGE, RED};
+use serde::Deserialize;
+
+/// Optional sidecar next to a document, `Document-0466.toml` for `Document-0466.png`
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DocumentMeta {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Free form, `YYYY-MM-DD` sorts correctly
+    pub date: Option<String>,
+    pub classification: Option<Classification>,
+    pub tags: Vec<String>,
+    /// Text of the document, only used for searching
+    pub body: String,
+    pub redactions: Vec<Redaction>,
+    pub links: Vec<DocumentLink>,
+}
+
+/// Clickable part of a page that opens another document
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentLink {
+    /// Name of the linked document
+    pub target: String,
+    /// Starting at 1
+    #[serde(default = "first_page")]
+    pub page: usize,
+    /// `[x, y, width, height]` in pixels of the page
+    pub rect: [f32; 4],
+}
+
+/// Black bar over a part of a page until it is revealed
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Redaction {
+    /// Revealed by the game master with this id
+    pub id: String,
+    /// Starting at 1
+    #[serde(default = "first_page")]
+    pub page: usize,
+    /// `[x, y, width, height]` in pixels of the page
+    pub rect: [f32; 4],
+    /// Revealed once the payload was used, puzzles have to be solved as well
+    pub payload: Option<String>,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+/// Ordered from the least to the most secret
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Classification {
+    Unclassified,
+    Restricted,
+    Confidential,
+    Secret,
+    TopSecret,
+}
+
+impl Classification {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Classification::Unclassified => "UNCLASSIFIED",
+            Classification::Restricted => "RESTRICTED",
+            Classification::Confidential => "CONFIDENTIAL",
+            Classification::Secret => "SECRET",
+            Classification::TopSecret => "TOP SECRET",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Classification::Unclassified => DARKGREEN,
+            Classification::Restricted => ORANGE,
+            Classification::Confidential => RED,
+            Classification::Secret | Classification::TopSecret => MAROON,
+        }
+    }
+}
+
+impl DocumentMeta {
+    /// Returns `Ok(None)` if the document has no sidecar
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(format!("Failed to read `{}`: {err}", path.display())),
+        };
+
+        toml::from_str(&content)
+            .map(Some)
+            .map_err(|err| format!("Failed to parse `{}`:\n{err}", path.display()))
+    }
+
+    /// Case-insensitive, `query` has to be lowercase already. Only the body is left out
+    /// with `include_body = false`.
+    pub fn matches(&self, query: &str, include_body: bool) -> bool {
+        let fields = [
+            self.title.as_deref(),
+            self.author.as_deref(),
+            self.date.as_deref(),
+            self.classification.as_ref().map(Classification::label),
+            include_body.then_some(self.body.as_str()),
+        ];
+
+        fields
+            .into_iter()
+            .flatten()
+            .chain(self.tags.iter().map(String::as_str))
+            .any(|field| field.to_lowercase().contains(query))
+    }
+}
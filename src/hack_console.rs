@@ -0,0 +1,253 @@
+use std::{collections::VecDeque, time::Duration};
+
+use macroquad::prelude::*;
+
+use crate::{
+    context::{AppContext, Clock},
+    hack_script::HackCommand,
+    input::InputEvent,
+    session::WindowSnapshot,
+    windows::{draw_window_top_bar, Window, WindowKind, WindowReturnAction, HEADER_HEIGHT},
+};
+
+const WIDTH: f32 = 900.0;
+const HEIGHT: f32 = 500.0;
+const FONT_SIZE: f32 = 30.0;
+const LINE_HEIGHT: f32 = 36.0;
+const PADDING: f32 = 20.0;
+const CHARS_PER_SECOND: f32 = 40.0;
+const PROGRESS_BAR_CELLS: usize = 20;
+const CLOSE_HINT: &str = "[ click to close ]";
+const PAUSED_TEXT: &str = "CONNECTION LOST";
+
+/// Command that is currently playing out
+enum Step {
+    Typing(String),
+    Sleeping(Duration),
+    Progress(String, Duration),
+}
+
+/// Plays the hack script line by line with a typewriter animation
+pub struct HackConsole {
+    /// Center of the screen
+    position: Vec2,
+    commands: VecDeque<HackCommand>,
+    lines: Vec<String>,
+    step: Option<Step>,
+    /// Time spent on the current step, does not grow while paused
+    step_elapsed: Duration,
+    /// Clock time of the last frame, `None` before the first one
+    last_update: Option<Duration>,
+    is_paused: bool,
+    is_focused: bool,
+}
+
+impl HackConsole {
+    /// A script without `start_minigame` starts it at the end, like the old fixed delay did
+    pub fn new(context: &AppContext, mut commands: Vec<HackCommand>) -> Self {
+        if !commands.contains(&HackCommand::StartMiniGame) {
+            commands.push(HackCommand::StartMiniGame);
+        }
+
+        HackConsole {
+            position: context.renderer.screen_size() * 0.5,
+            commands: commands.into(),
+            lines: vec![],
+            step: None,
+            step_elapsed: Duration::ZERO,
+            last_update: None,
+            is_paused: false,
+            is_focused: false,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.step.is_none() && self.commands.is_empty()
+    }
+
+    /// Moves the script forward, returns an action when a command needs the system
+    fn advance(&mut self, now: Duration) -> WindowReturnAction {
+        if let Some(last) = self.last_update.filter(|_| !self.is_paused) {
+            self.step_elapsed += now.saturating_sub(last);
+        }
+        self.last_update = Some(now);
+        if self.is_paused {
+            return WindowReturnAction::None;
+        }
+
+        let step_done = match &self.step {
+            Some(Step::Typing(text)) => self.typed_chars() >= text.chars().count(),
+            Some(Step::Sleeping(duration)) => self.step_elapsed >= *duration,
+            Some(Step::Progress(_, duration)) => self.step_elapsed >= *duration,
+            None => true,
+        };
+        if !step_done {
+            return WindowReturnAction::None;
+        }
+
+        match self.step.take() {
+            Some(Step::Typing(text)) => self.lines.extend(text.lines().map(str::to_string)),
+            Some(Step::Progress(label, _)) => self.lines.push(progress_line(&label, 1.0)),
+            Some(Step::Sleeping(_)) | None => {}
+        }
+        self.step_elapsed = Duration::ZERO;
+
+        match self.commands.pop_front() {
+            Some(HackCommand::Print(text)) => self.step = Some(Step::Typing(text)),
+            Some(HackCommand::Sleep(duration)) => self.step = Some(Step::Sleeping(duration)),
+            Some(HackCommand::Progress(label, duration)) => {
+                self.step = Some(Step::Progress(label, duration))
+            }
+            Some(HackCommand::OpenDocument(name)) => return WindowReturnAction::OpenDocument(name),
+            Some(HackCommand::StartMiniGame) => return WindowReturnAction::StartMiniGame,
+            None => {}
+        }
+
+        WindowReturnAction::None
+    }
+
+    fn typed_chars(&self) -> usize {
+        (self.step_elapsed.as_secs_f32() * CHARS_PER_SECOND) as usize
+    }
+
+    /// Finished lines followed by the step in progress
+    fn visible_lines(&self, clock: &Clock) -> Vec<String> {
+        let mut lines = self.lines.clone();
+        match &self.step {
+            Some(Step::Typing(text)) => {
+                let typed = text.chars().take(self.typed_chars()).collect::<String>();
+                lines.extend(typed.lines().map(str::to_string));
+                if typed.is_empty() || typed.ends_with('\n') {
+                    lines.push(String::new());
+                }
+            }
+            Some(Step::Progress(label, duration)) => {
+                let percent = self.step_elapsed.as_secs_f32() / duration.as_secs_f32().max(0.001);
+                lines.push(progress_line(label, percent.min(1.0)));
+            }
+            Some(Step::Sleeping(_)) | None => {}
+        }
+
+        if self.is_paused {
+            lines.push(PAUSED_TEXT.to_string());
+        } else if self.is_finished() {
+            lines.push(String::new());
+            lines.push(CLOSE_HINT.to_string());
+        } else if let Some(last) = lines.last_mut() {
+            // Blinking cursor
+            if clock.cursor_visible() {
+                last.push('_');
+            }
+        } else if clock.cursor_visible() {
+            lines.push("_".to_string());
+        }
+
+        lines
+    }
+}
+
+/// E.g. `Decrypting [##########          ] 50%`
+fn progress_line(label: &str, percent: f32) -> String {
+    let filled = (percent * PROGRESS_BAR_CELLS as f32) as usize;
+    format!(
+        "{label} [{}{}] {:>3}%",
+        "#".repeat(filled),
+        " ".repeat(PROGRESS_BAR_CELLS - filled),
+        (percent * 100.0) as u32
+    )
+}
+
+impl Window for HackConsole {
+    fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    fn top_left(&self) -> Vec2 {
+        self.position() - vec2(WIDTH, HEIGHT) * 0.5
+    }
+
+    fn size(&self) -> Vec2 {
+        Vec2::new(WIDTH, HEIGHT)
+    }
+
+    fn draw(&mut self, context: &AppContext) {
+        let theme = &context.theme;
+        let tl = self.top_left();
+        context.renderer.draw_outlined_box(
+            Rect::new(tl.x, tl.y, WIDTH, HEIGHT),
+            5.0,
+            theme.foreground,
+            theme.foreground,
+        );
+        let header = Rect::new(tl.x, tl.y, WIDTH, HEADER_HEIGHT);
+        draw_window_top_bar(context, &self.title(), 30.0, header, self.is_focused);
+
+        // Only the newest lines that fit are shown
+        let max_lines = ((HEIGHT - HEADER_HEIGHT - 2.0 * PADDING) / LINE_HEIGHT) as usize;
+        let lines = self.visible_lines(&context.clock);
+        let first = lines.len().saturating_sub(max_lines);
+        let mut y = tl.y + HEADER_HEIGHT + PADDING + FONT_SIZE;
+        for line in &lines[first..] {
+            context
+                .renderer
+                .draw_text(line, tl.x + PADDING, y, FONT_SIZE, theme.background);
+            y += LINE_HEIGHT;
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        true
+    }
+
+    fn set_visibility(&mut self, _value: bool) {}
+
+    fn set_focused(&mut self, focused: bool) {
+        self.is_focused = focused;
+    }
+
+    fn update(&mut self, context: &AppContext) -> WindowReturnAction {
+        self.position = context.renderer.screen_size() * 0.5;
+        if self.is_finished() {
+            return WindowReturnAction::None;
+        }
+
+        self.advance(context.clock.now())
+    }
+
+    fn handle_input(&mut self, _context: &AppContext, event: InputEvent) -> WindowReturnAction {
+        match event {
+            InputEvent::MouseDown {
+                button: MouseButton::Left,
+                ..
+            } if self.is_finished() => WindowReturnAction::Close,
+            _ => WindowReturnAction::None,
+        }
+    }
+
+    fn icon(&self, context: &AppContext) -> Option<Texture2D> {
+        context.textures.popup()
+    }
+
+    fn kind(&self) -> WindowKind {
+        WindowKind::HackConsole
+    }
+
+    fn title(&self) -> String {
+        "Hack console".to_string()
+    }
+
+    fn snapshot(&self) -> Option<WindowSnapshot> {
+        None
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.is_paused = paused;
+    }
+
+    fn contains_pos(&self, pos: Vec2) -> bool {
+        let tl = self.top_left();
+        let br = tl + vec2(WIDTH, HEIGHT);
+
+        pos.x >= tl.x && pos.x <= br.x && pos.y >= tl.y && pos.y <= br.y
+    }
+}
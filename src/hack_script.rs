@@ -0,0 +1,204 @@
+use std::{iter::Peekable, str::Chars, time::Duration};
+
+use crate::payload::ID_PREFIX;
+
+/// One statement of a hack payload, e.g. `print("Starting attack!");`
+#[derive(Clone, PartialEq, Debug)]
+pub enum HackCommand {
+    /// Typed out on a new line of the console
+    Print(String),
+    Sleep(Duration),
+    /// Labeled progress bar that fills up over the given time
+    Progress(String, Duration),
+    OpenDocument(String),
+    StartMiniGame,
+}
+
+enum Argument {
+    Text(String),
+    Number(u64),
+}
+
+/// Parses the verified body of a hack payload. Statements are separated by `;` or new lines,
+/// lines starting with `//` are comments and the `id:` line is skipped.
+pub fn parse(body: &str) -> Result<Vec<HackCommand>, String> {
+    let mut commands = vec![];
+    for (line_index, line) in body.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") || line.starts_with(ID_PREFIX) {
+            continue;
+        }
+
+        let mut chars = line.chars().peekable();
+        loop {
+            skip_whitespace(&mut chars);
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let command = parse_statement(&mut chars)
+                .map_err(|err| format!("Line {}: {err}", line_index + 1))?;
+            commands.push(command);
+
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                None | Some(';') => {}
+                Some(char) => {
+                    return Err(format!(
+                        "Line {}: expected `;` but found `{char}`",
+                        line_index + 1
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(commands)
+}
+
+fn parse_statement(chars: &mut Peekable<Chars>) -> Result<HackCommand, String> {
+    let mut name = String::new();
+    while let Some(&char) = chars.peek() {
+        if !(char.is_ascii_alphanumeric() || char == '_') {
+            break;
+        }
+        name.push(char);
+        chars.next();
+    }
+    if name.is_empty() {
+        return Err("expected a command".to_string());
+    }
+
+    skip_whitespace(chars);
+    if chars.next() != Some('(') {
+        return Err(format!("expected `(` after `{name}`"));
+    }
+    let arguments = parse_arguments(chars)?;
+
+    // Names are case-insensitive, the original payload used `Print`
+    match (name.to_ascii_lowercase().as_str(), arguments.as_slice()) {
+        ("print", [Argument::Text(text)]) => Ok(HackCommand::Print(text.clone())),
+        ("sleep", [Argument::Number(ms)]) => Ok(HackCommand::Sleep(Duration::from_millis(*ms))),
+        ("progress", [Argument::Text(label), Argument::Number(ms)]) => Ok(HackCommand::Progress(
+            label.clone(),
+            Duration::from_millis(*ms),
+        )),
+        ("open_document", [Argument::Text(name)]) => Ok(HackCommand::OpenDocument(name.clone())),
+        ("start_minigame", []) => Ok(HackCommand::StartMiniGame),
+        ("print" | "sleep" | "progress" | "open_document" | "start_minigame", _) => {
+            Err(format!("wrong arguments for `{name}`"))
+        }
+        _ => Err(format!("unknown command `{name}`")),
+    }
+}
+
+/// Parses everything up to and including the closing parenthesis
+fn parse_arguments(chars: &mut Peekable<Chars>) -> Result<Vec<Argument>, String> {
+    let mut arguments = vec![];
+    loop {
+        skip_whitespace(chars);
+        match chars.peek() {
+            Some(')') if arguments.is_empty() => {
+                chars.next();
+                return Ok(arguments);
+            }
+            Some('"') => {
+                chars.next();
+                arguments.push(Argument::Text(parse_string(chars)?));
+            }
+            Some(char) if char.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(&char) = chars.peek() {
+                    if !char.is_ascii_digit() {
+                        break;
+                    }
+                    number.push(char);
+                    chars.next();
+                }
+                let number = number
+                    .parse()
+                    .map_err(|_| format!("number `{number}` is too large"))?;
+                arguments.push(Argument::Number(number));
+            }
+            Some(char) => return Err(format!("unexpected `{char}` in arguments")),
+            None => return Err("missing `)`".to_string()),
+        }
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some(')') => return Ok(arguments),
+            Some(char) => return Err(format!("expected `,` or `)` but found `{char}`")),
+            None => return Err("missing `)`".to_string()),
+        }
+    }
+}
+
+/// Parses a string after its opening quote, supports `\"`, `\\` and `\n`
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut string = String::new();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' => return Ok(string),
+            '\\' => match chars.next() {
+                Some('n') => string.push('\n'),
+                Some(escaped @ ('"' | '\\')) => string.push(escaped),
+                Some(escaped) => return Err(format!("unknown escape `\\{escaped}`")),
+                None => break,
+            },
+            _ => string.push(char),
+        }
+    }
+
+    Err("unterminated string".to_string())
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|char| char.is_whitespace()) {
+        chars.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_all_commands() {
+        let body = r#"
+id: hack
+// Comments are skipped
+Print("Connecting \"mainframe\"...\n");
+sleep(500); progress("Decrypting", 2000)
+open_document("Document-0466");
+start_minigame ( );
+"#;
+        assert_eq!(
+            parse(body),
+            Ok(vec![
+                HackCommand::Print("Connecting \"mainframe\"...\n".to_string()),
+                HackCommand::Sleep(Duration::from_millis(500)),
+                HackCommand::Progress("Decrypting".to_string(), Duration::from_millis(2000)),
+                HackCommand::OpenDocument("Document-0466".to_string()),
+                HackCommand::StartMiniGame,
+            ])
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |body: &str| parse(body).unwrap_err();
+        assert_eq!(error("\nreboot();"), "Line 2: unknown command `reboot`");
+        assert_eq!(
+            error("sleep(\"500\");"),
+            "Line 1: wrong arguments for `sleep`"
+        );
+        assert_eq!(
+            error("print(\"a\") print(\"b\");"),
+            "Line 1: expected `;` but found `p`"
+        );
+        assert_eq!(error("print(\"a);"), "Line 1: unterminated string");
+        assert_eq!(error("print(\"\\t\");"), "Line 1: unknown escape `\\t`");
+        assert_eq!(error("sleep(500"), "Line 1: missing `)`");
+    }
+}
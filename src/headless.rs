@@ -0,0 +1,285 @@
+use std::{
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use macroquad::prelude::*;
+
+use crate::{
+    context::Clock,
+    control::{ControlCommand, StatusReport},
+    input::{InputEvent, InputSource, InputState, Modifiers},
+    render::Renderer,
+    scenario::{Scenario, ScenarioError},
+    system::{Backend, EscOS, TextureStorage},
+    usb::{UsbEvent, UsbWatcher},
+    vfs::VirtualFs,
+};
+
+/// Simulated time between two frames, about 60 frames per second like the terminal
+pub const FRAME_TIME: Duration = Duration::from_millis(16);
+const SCREEN_SIZE: Vec2 = vec2(1920.0, 1080.0);
+
+/// Draws nothing. Text is measured as if every character was half as wide as it is high,
+/// close enough to the default font for the layout of the windows.
+pub struct HeadlessRenderer {
+    pub screen_size: Vec2,
+}
+
+impl Renderer for HeadlessRenderer {
+    fn screen_size(&self) -> Vec2 {
+        self.screen_size
+    }
+
+    fn supports_textures(&self) -> bool {
+        false
+    }
+
+    fn clear_background(&self, _color: Color) {}
+
+    fn draw_rectangle(&self, _x: f32, _y: f32, _width: f32, _height: f32, _color: Color) {}
+
+    fn draw_rectangle_lines(
+        &self,
+        _x: f32,
+        _y: f32,
+        _width: f32,
+        _height: f32,
+        _thickness: f32,
+        _color: Color,
+    ) {
+    }
+
+    fn draw_circle(&self, _x: f32, _y: f32, _radius: f32, _color: Color) {}
+
+    fn draw_text(&self, _text: &str, _x: f32, _y: f32, _font_size: f32, _color: Color) {}
+
+    fn draw_multiline_text(
+        &self,
+        _text: &str,
+        _x: f32,
+        _y: f32,
+        _font_size: f32,
+        _line_distance_factor: Option<f32>,
+        _color: Color,
+    ) {
+    }
+
+    fn draw_texture(&self, _texture: &Texture2D, _x: f32, _y: f32, _color: Color) {}
+
+    fn draw_texture_ex(
+        &self,
+        _texture: &Texture2D,
+        _x: f32,
+        _y: f32,
+        _color: Color,
+        _params: DrawTextureParams,
+    ) {
+    }
+
+    fn measure_text(&self, text: &str, font_size: u16, font_scale: f32) -> TextDimensions {
+        let size = font_size as f32 * font_scale;
+        TextDimensions {
+            width: text.chars().count() as f32 * size * 0.5,
+            height: size,
+            offset_y: size * 0.75,
+        }
+    }
+}
+
+/// Plays back the events sent through the channel, one frame's worth at a time
+pub struct ScriptedInput {
+    events: Receiver<InputEvent>,
+    state: InputState,
+}
+
+impl ScriptedInput {
+    pub fn new(events: Receiver<InputEvent>) -> Self {
+        ScriptedInput {
+            events,
+            state: InputState::default(),
+        }
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self) -> Vec<InputEvent> {
+        let events = self.events.try_iter().collect::<Vec<_>>();
+        for event in &events {
+            match *event {
+                InputEvent::MouseDown { pos, .. }
+                | InputEvent::MouseUp { pos, .. }
+                | InputEvent::MouseMove { pos, .. }
+                | InputEvent::Drag { pos, .. }
+                | InputEvent::DoubleClick { pos }
+                | InputEvent::Wheel { pos, .. } => self.state.mouse_pos = pos,
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn state(&self) -> InputState {
+        self.state
+    }
+}
+
+/// The whole room without a window: scripted input, USB drives plugged in by the caller and
+/// a clock that only moves frame by frame. Meant for testing the room logic.
+pub struct Simulation {
+    esc_os: EscOS,
+    input: Sender<InputEvent>,
+    usb: Sender<UsbEvent>,
+}
+
+impl Simulation {
+    /// udiskie is never started, there are no real drives to mount
+    pub fn new(scenario_path: &Path) -> Result<Self, ScenarioError> {
+        Self::with_documents(scenario_path, TextureStorage::default())
+    }
+
+    /// `documents` are built with `TextureStorage::add_document` from pages without textures
+    pub fn with_documents(
+        scenario_path: &Path,
+        documents: TextureStorage,
+    ) -> Result<Self, ScenarioError> {
+        let mut scenario = Scenario::load(scenario_path)?;
+        scenario.usb.udiskie = false;
+
+        let (input, events) = mpsc::channel();
+        let (usb_watcher, usb) = UsbWatcher::scripted();
+        let backend = Backend {
+            renderer: Box::new(HeadlessRenderer {
+                screen_size: SCREEN_SIZE,
+            }),
+            input: Box::new(ScriptedInput::new(events)),
+            usb_watcher,
+            clock: Clock::fake(),
+        };
+        let esc_os = EscOS::with_backend(scenario_path, scenario, documents, backend)?;
+
+        Ok(Simulation { esc_os, input, usb })
+    }
+
+    /// Runs one frame, `FRAME_TIME` after the previous one
+    pub fn step(&mut self) {
+        self.esc_os.advance_clock(FRAME_TIME);
+        self.esc_os.tick();
+    }
+
+    pub fn run_for(&mut self, duration: Duration) {
+        let frames = duration.as_millis().div_ceil(FRAME_TIME.as_millis());
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    /// Runs frames until the condition holds, returns false if it did not within `timeout`
+    pub fn run_until(
+        &mut self,
+        timeout: Duration,
+        condition: impl Fn(&StatusReport) -> bool,
+    ) -> bool {
+        let mut elapsed = Duration::ZERO;
+        while !condition(&self.status()) {
+            if elapsed >= timeout {
+                return false;
+            }
+            self.step();
+            elapsed += FRAME_TIME;
+        }
+        true
+    }
+
+    /// The event is handled by the next frame
+    pub fn send(&self, event: InputEvent) {
+        self.input
+            .send(event)
+            .expect("the input is owned by the simulation");
+    }
+
+    pub fn press_key(&self, key: KeyCode) {
+        self.press_key_with(key, Modifiers::default());
+    }
+
+    pub fn press_key_with(&self, key: KeyCode, modifiers: Modifiers) {
+        self.send(InputEvent::KeyDown {
+            key,
+            modifiers,
+            repeat: false,
+        });
+        self.send(InputEvent::KeyUp { key, modifiers });
+    }
+
+    pub fn type_text(&self, text: &str) {
+        for char in text.chars() {
+            self.send(InputEvent::Text(char));
+        }
+    }
+
+    pub fn click(&self, pos: Vec2) {
+        self.click_with(pos, Modifiers::default());
+    }
+
+    pub fn click_with(&self, pos: Vec2, modifiers: Modifiers) {
+        self.send(InputEvent::MouseDown {
+            button: MouseButton::Left,
+            pos,
+            modifiers,
+        });
+        self.send(InputEvent::MouseUp {
+            button: MouseButton::Left,
+            pos,
+        });
+    }
+
+    /// Presses the left button at `from` and releases it at `to`
+    pub fn drag(&self, from: Vec2, to: Vec2) {
+        self.send(InputEvent::MouseDown {
+            button: MouseButton::Left,
+            pos: from,
+            modifiers: Modifiers::default(),
+        });
+        self.send(InputEvent::Drag {
+            button: MouseButton::Left,
+            pos: to,
+            delta: to - from,
+        });
+        self.send(InputEvent::MouseUp {
+            button: MouseButton::Left,
+            pos: to,
+        });
+    }
+
+    /// The volume is a folder with the files of the drive
+    pub fn plug_usb(&self, volume: &Path) {
+        self.usb
+            .send(UsbEvent::Mounted(volume.to_path_buf()))
+            .expect("the USB watcher is owned by the simulation");
+    }
+
+    pub fn unplug_usb(&self, volume: &Path) {
+        self.usb
+            .send(UsbEvent::Removed(volume.to_path_buf()))
+            .expect("the USB watcher is owned by the simulation");
+    }
+
+    /// Like a request to the control server, handled right away
+    pub fn control(&mut self, command: ControlCommand) {
+        self.esc_os.handle_control_command(command);
+    }
+
+    /// Encryption, redactions and locks of the files
+    pub fn filesystem(&self) -> &VirtualFs {
+        self.esc_os.filesystem()
+    }
+
+    pub fn status(&self) -> StatusReport {
+        self.esc_os.status_report()
+    }
+
+    pub fn screen_size(&self) -> Vec2 {
+        SCREEN_SIZE
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use macroquad::{
+    input::utils::{register_input_subscriber, repeat_all_miniquad_input},
+    miniquad::{EventHandler, KeyMods, TouchPhase},
+    prelude::*,
+};
+
+/// Seconds between two left clicks to count as a double click
+const DOUBLE_CLICK_TIME: f64 = 0.4;
+/// The second click of a double click may be this many pixels away from the first
+const DOUBLE_CLICK_DISTANCE: f32 = 6.0;
+
+/// Keys held during an event
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+/// Input that is not an event, as of the last event
+#[derive(Clone, Copy, Default, Debug)]
+pub struct InputState {
+    pub mouse_pos: Vec2,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputEvent {
+    /// `repeat` is set for the repeats of a held key
+    KeyDown {
+        key: KeyCode,
+        modifiers: Modifiers,
+        repeat: bool,
+    },
+    KeyUp {
+        key: KeyCode,
+        modifiers: Modifiers,
+    },
+    /// Typed text, without control characters like backspace or enter
+    Text(char),
+    MouseDown {
+        button: MouseButton,
+        pos: Vec2,
+        modifiers: Modifiers,
+    },
+    MouseUp {
+        button: MouseButton,
+        pos: Vec2,
+    },
+    /// No button is held
+    MouseMove {
+        pos: Vec2,
+        delta: Vec2,
+    },
+    /// Moved while `button` is held
+    Drag {
+        button: MouseButton,
+        pos: Vec2,
+        delta: Vec2,
+    },
+    /// Sent after the `MouseDown` of the second left click
+    DoubleClick {
+        pos: Vec2,
+    },
+    /// Positive `delta.y` scrolls up, positive `delta.x` scrolls left
+    Wheel {
+        pos: Vec2,
+        delta: Vec2,
+        modifiers: Modifiers,
+    },
+    TouchStart {
+        id: u64,
+        pos: Vec2,
+    },
+    TouchMove {
+        id: u64,
+        pos: Vec2,
+        delta: Vec2,
+    },
+    /// Also sent when the touch was cancelled
+    TouchEnd {
+        id: u64,
+        pos: Vec2,
+    },
+}
+
+/// Where the input events of a frame come from
+pub trait InputSource {
+    /// Has to be called every frame, returns the events in the order they happened
+    fn poll(&mut self) -> Vec<InputEvent>;
+
+    fn state(&self) -> InputState;
+}
+
+/// Turns what macroquad received since the last frame into `InputEvent`s, in the order it
+/// happened
+pub struct InputCollector {
+    subscriber: usize,
+    events: Vec<InputEvent>,
+    mouse_pos: Vec2,
+    buttons_down: HashSet<MouseButton>,
+    /// Time and position of the last left click
+    last_click: Option<(f64, Vec2)>,
+    touches: HashMap<u64, Vec2>,
+    modifiers: Modifiers,
+}
+
+impl InputCollector {
+    pub fn new() -> Self {
+        let (x, y) = mouse_position();
+        InputCollector {
+            subscriber: register_input_subscriber(),
+            events: vec![],
+            mouse_pos: vec2(x, y),
+            buttons_down: HashSet::new(),
+            last_click: None,
+            touches: HashMap::new(),
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    /// Pressing a modifier key does not always report the key itself in the modifiers
+    fn update_modifiers(&mut self, key: KeyCode, modifiers: KeyMods, is_down: bool) {
+        self.modifiers = Modifiers {
+            shift: modifiers.shift,
+            ctrl: modifiers.ctrl,
+            alt: modifiers.alt,
+            logo: modifiers.logo,
+        };
+        match key {
+            KeyCode::LeftShift | KeyCode::RightShift => self.modifiers.shift = is_down,
+            KeyCode::LeftControl | KeyCode::RightControl => self.modifiers.ctrl = is_down,
+            KeyCode::LeftAlt | KeyCode::RightAlt => self.modifiers.alt = is_down,
+            KeyCode::LeftSuper | KeyCode::RightSuper => self.modifiers.logo = is_down,
+            _ => {}
+        }
+    }
+}
+
+impl Default for InputCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSource for InputCollector {
+    fn poll(&mut self) -> Vec<InputEvent> {
+        repeat_all_miniquad_input(self, self.subscriber);
+        mem::take(&mut self.events)
+    }
+
+    fn state(&self) -> InputState {
+        InputState {
+            mouse_pos: self.mouse_pos,
+        }
+    }
+}
+
+impl EventHandler for InputCollector {
+    fn update(&mut self) {}
+
+    fn draw(&mut self) {}
+
+    fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        let pos = vec2(x, y);
+        let delta = pos - self.mouse_pos;
+        self.mouse_pos = pos;
+        // Left wins if more buttons are held
+        let held = [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+            .into_iter()
+            .find(|button| self.buttons_down.contains(button));
+        self.events.push(match held {
+            Some(button) => InputEvent::Drag { button, pos, delta },
+            None => InputEvent::MouseMove { pos, delta },
+        });
+    }
+
+    fn mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.events.push(InputEvent::Wheel {
+            pos: self.mouse_pos,
+            delta: vec2(x, y),
+            modifiers: self.modifiers,
+        });
+    }
+
+    fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        let pos = vec2(x, y);
+        self.mouse_pos = pos;
+        self.buttons_down.insert(button);
+        self.events.push(InputEvent::MouseDown {
+            button,
+            pos,
+            modifiers: self.modifiers,
+        });
+
+        if button != MouseButton::Left {
+            return;
+        }
+        let now = get_time();
+        let is_double = self.last_click.is_some_and(|(time, last_pos)| {
+            now - time < DOUBLE_CLICK_TIME && last_pos.distance(pos) < DOUBLE_CLICK_DISTANCE
+        });
+        if is_double {
+            self.events.push(InputEvent::DoubleClick { pos });
+            // A third click starts over
+            self.last_click = None;
+        } else {
+            self.last_click = Some((now, pos));
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        let pos = vec2(x, y);
+        self.mouse_pos = pos;
+        self.buttons_down.remove(&button);
+        self.events.push(InputEvent::MouseUp { button, pos });
+    }
+
+    fn char_event(&mut self, character: char, _modifiers: KeyMods, _repeat: bool) {
+        if !character.is_control() {
+            self.events.push(InputEvent::Text(character));
+        }
+    }
+
+    fn key_down_event(&mut self, key: KeyCode, modifiers: KeyMods, repeat: bool) {
+        self.update_modifiers(key, modifiers, true);
+        self.events.push(InputEvent::KeyDown {
+            key,
+            modifiers: self.modifiers,
+            repeat,
+        });
+    }
+
+    fn key_up_event(&mut self, key: KeyCode, modifiers: KeyMods) {
+        self.update_modifiers(key, modifiers, false);
+        self.events.push(InputEvent::KeyUp {
+            key,
+            modifiers: self.modifiers,
+        });
+    }
+
+    fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        let pos = vec2(x, y);
+        let event = match phase {
+            TouchPhase::Started => {
+                self.touches.insert(id, pos);
+                InputEvent::TouchStart { id, pos }
+            }
+            TouchPhase::Moved => {
+                let last = self.touches.insert(id, pos).unwrap_or(pos);
+                InputEvent::TouchMove {
+                    id,
+                    pos,
+                    delta: pos - last,
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+                InputEvent::TouchEnd { id, pos }
+            }
+        };
+        self.events.push(event);
+    }
+}
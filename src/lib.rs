@@ -0,0 +1,25 @@
+pub mod context;
+pub mod control;
+pub mod document;
+pub mod document_list;
+pub mod document_meta;
+pub mod hack_console;
+pub mod hack_script;
+pub mod headless;
+pub mod input;
+pub mod login;
+pub mod minigame;
+pub mod payload;
+pub mod popup;
+pub mod render;
+pub mod resume_prompt;
+pub mod scenario;
+pub mod session;
+pub mod system;
+pub mod terminal;
+pub mod text_document;
+pub mod usb;
+pub mod vfs;
+pub mod window_chrome;
+pub mod window_manager;
+pub mod windows;
@@ -1,130 +1,136 @@
 use crate::{
-    popup::PopUp,
-    system::{BG_COLOR, FG_COLOR},
-    windows::*,
-};
-use macroquad::{
-    prelude::*,
-    ui::{
-        root_ui,
-        widgets::{Button, InputText},
-    },
+    context::AppContext, input::InputEvent, popup::PopUp, scenario::LoginConfig,
+    session::WindowSnapshot, windows::*,
 };
+use macroquad::prelude::*;
+
+const BUTTON_SIZE: Vec2 = vec2(100.0, 50.0);
+const FONT_SIZE: f32 = 30.0;
 
 pub struct LoginWindow {
+    /// Center of the screen
+    position: Vec2,
     width: f32,
     height: f32,
     password_data: String,
     input_size: Vec2,
     is_visible: bool,
-    login_button_clicked: bool,
+    /// Typing goes into the password field
+    is_focused: bool,
+    error_text: String,
 }
 
-impl Window for LoginWindow {
-    async fn new_boxed() -> Box<dyn Window> {
-        Box::new(LoginWindow {
+impl LoginWindow {
+    pub fn new(context: &AppContext, config: &LoginConfig) -> Self {
+        LoginWindow {
+            position: context.renderer.screen_size() * 0.5,
             width: 500.0,
             height: 300.0,
             password_data: String::new(),
             input_size: Vec2::new(300.0, 60.0),
             is_visible: true,
-            login_button_clicked: false,
-        })
+            is_focused: false,
+            error_text: config.error_text.clone(),
+        }
     }
 
+    fn button_rect(&self) -> Rect {
+        let position = self.position() + Vec2::new(-BUTTON_SIZE.x * 0.5, self.height * 0.25);
+        Rect::new(position.x, position.y, BUTTON_SIZE.x, BUTTON_SIZE.y)
+    }
+
+    /// The password is never right, trying shows the error of the room
+    fn log_in(&mut self, context: &AppContext) -> WindowReturnAction {
+        self.password_data.clear();
+        WindowReturnAction::NewWindow(Box::new(PopUp::new_with_text(
+            context,
+            self.error_text.clone(),
+        )))
+    }
+}
+
+impl Window for LoginWindow {
     /// Alway in center
     fn position(&self) -> Vec2 {
-        Vec2::new(screen_width() * 0.5, screen_height() * 0.5)
+        self.position
     }
 
     fn size(&self) -> Vec2 {
         Vec2::new(self.width, self.height)
     }
 
-    fn draw(&mut self) {
+    fn draw(&mut self, context: &AppContext) {
+        let theme = &context.theme;
         // Draw box
-        draw_outlined_box(
-            self.position().x - self.width * 0.5,
-            self.position().y - self.height * 0.5,
-            self.width,
-            self.height,
+        context.renderer.draw_outlined_box(
+            Rect::new(
+                self.position().x - self.width * 0.5,
+                self.position().y - self.height * 0.5,
+                self.width,
+                self.height,
+            ),
             5.0,
-            BG_COLOR,
-            FG_COLOR,
+            theme.background,
+            theme.foreground,
         );
 
         // Label
-        draw_text(
+        context.renderer.draw_text(
             "Enter password",
             self.position().x - self.input_size.x * 0.5,
             self.position().y - self.input_size.y,
             50.0,
-            FG_COLOR,
+            theme.foreground,
         );
 
-        // Draw input
-        let input_style = root_ui()
-            .style_builder()
-            .font_size(30)
-            .color(BLANK)
-            .color_hovered(BLANK)
-            .color_clicked(BLANK)
-            .build();
-        let mut skin = root_ui().default_skin();
-        skin.editbox_style = input_style;
-        root_ui().push_skin(&skin);
-        InputText::new(1)
-            .position(self.position() - Vec2::new(self.input_size.x * 0.5, self.input_size.y * 0.4))
-            .size(self.input_size)
-            .ratio(5.0)
-            .password(true)
-            .ui(&mut root_ui(), &mut self.password_data);
-        root_ui().pop_skin();
-
         let bigger_x = self.input_size.x * 1.1;
         let bigger_y = self.input_size.y * 1.1;
-        draw_outlined_box(
-            self.position().x - bigger_x * 0.5,
-            self.position().y - bigger_y * 0.5,
-            bigger_x,
-            bigger_y,
+        context.renderer.draw_outlined_box(
+            Rect::new(
+                self.position().x - bigger_x * 0.5,
+                self.position().y - bigger_y * 0.5,
+                bigger_x,
+                bigger_y,
+            ),
             5.0,
-            BG_COLOR,
-            FG_COLOR,
+            theme.background,
+            theme.foreground,
+        );
+
+        // Draw input
+        let cursor = if self.is_focused && context.clock.cursor_visible() {
+            "_"
+        } else {
+            ""
+        };
+        let password = format!("{}{cursor}", "*".repeat(self.password_data.chars().count()));
+        context.renderer.draw_text(
+            &password,
+            self.position().x - self.input_size.x * 0.5 + 10.0,
+            self.position().y + FONT_SIZE * 0.35,
+            FONT_SIZE,
+            theme.foreground,
         );
 
-        let (button_width, button_height) = (100.0, 50.0);
-
-        let button_style = root_ui()
-            .style_builder()
-            .color(BLANK)
-            .color_hovered(BLANK)
-            .text_color(BG_COLOR)
-            .text_color_hovered(BG_COLOR)
-            .build();
-        let mut skin = root_ui().default_skin();
-        skin.button_style = button_style;
-        root_ui().push_skin(&skin);
-
-        let position = self.position() + Vec2::new(-button_width * 0.5, self.height * 0.25);
-        let (bigger_width, bigger_height) = (button_width * 1.05, button_height * 1.05);
+        let (bigger_width, bigger_height) = (BUTTON_SIZE.x * 1.05, BUTTON_SIZE.y * 1.05);
         let box_position = self.position() + Vec2::new(-bigger_width * 0.5, self.height * 0.245);
-        draw_outlined_box(
-            box_position.x,
-            box_position.y,
-            bigger_width,
-            bigger_height,
+        context.renderer.draw_outlined_box(
+            Rect::new(box_position.x, box_position.y, bigger_width, bigger_height),
             5.0,
-            FG_COLOR,
-            FG_COLOR,
+            theme.foreground,
+            theme.foreground,
+        );
+        let button = self.button_rect();
+        let label = context
+            .renderer
+            .measure_text("Log-in", FONT_SIZE as u16, 1.0);
+        context.renderer.draw_text(
+            "Log-in",
+            button.center().x - label.width * 0.5,
+            button.center().y + label.offset_y * 0.5,
+            FONT_SIZE,
+            theme.background,
         );
-        let button_size = Vec2::new(button_width, button_height);
-        self.login_button_clicked = Button::new("Log-in")
-            .position(position)
-            .size(button_size)
-            .ui(&mut root_ui());
-
-        root_ui().pop_skin();
     }
 
     fn top_left(&self) -> Vec2 {
@@ -139,22 +145,55 @@ impl Window for LoginWindow {
         self.is_visible = value;
     }
 
-    fn handle_input(&mut self, event: InputEvent) -> WindowReturnAction {
+    fn set_focused(&mut self, focused: bool) {
+        self.is_focused = focused;
+    }
+
+    fn update(&mut self, context: &AppContext) -> WindowReturnAction {
+        self.position = context.renderer.screen_size() * 0.5;
+        WindowReturnAction::None
+    }
+
+    fn handle_input(&mut self, context: &AppContext, event: InputEvent) -> WindowReturnAction {
         match event {
-            InputEvent::LeftMouse(_pos, _) => {
-                if self.login_button_clicked {
-                    WindowReturnAction::NewWindow(Box::new(PopUp::new_with_text(
-                        "Error:\nLogin is disabled during emergency\nprotocol!".to_string(),
-                    )))
-                } else {
-                    WindowReturnAction::None
-                }
+            InputEvent::MouseDown {
+                button: MouseButton::Left,
+                pos,
+                ..
+            } if self.button_rect().contains(pos) => self.log_in(context),
+            InputEvent::Text(char) => {
+                self.password_data.push(char);
+                WindowReturnAction::None
+            }
+            InputEvent::KeyDown {
+                key: KeyCode::Backspace,
+                ..
+            } => {
+                self.password_data.pop();
+                WindowReturnAction::None
             }
+            InputEvent::KeyDown {
+                key: KeyCode::Enter | KeyCode::KpEnter,
+                repeat: false,
+                ..
+            } => self.log_in(context),
             _ => WindowReturnAction::None,
         }
     }
 
-    fn icon(&self) -> Option<Texture2D> {
+    fn icon(&self, _context: &AppContext) -> Option<Texture2D> {
+        None
+    }
+
+    fn kind(&self) -> WindowKind {
+        WindowKind::Login
+    }
+
+    fn title(&self) -> String {
+        "Login".to_string()
+    }
+
+    fn snapshot(&self) -> Option<WindowSnapshot> {
         None
     }
 
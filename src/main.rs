@@ -1,14 +1,10 @@
-mod document;
-mod document_list;
-mod login;
-mod minigame;
-mod popup;
-mod system;
-mod windows;
+use std::path::{Path, PathBuf};
 
+use esc_terminal::payload;
+use esc_terminal::scenario::DEFAULT_SCENARIO_PATH;
+use esc_terminal::system::EscOS;
 use macroquad::prelude::*;
 use macroquad::window::Conf as WindowConf;
-use system::EscOS;
 
 // Constants definition
 // STYLE
@@ -21,12 +17,43 @@ fn window_conf() -> WindowConf {
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let mut esc_os = EscOS::new().await;
+fn scenario_path(arg: Option<String>) -> PathBuf {
+    arg.map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SCENARIO_PATH))
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let first_arg = args.next();
+
+    // `--sign <file> [scenario]` signs a hack file without opening the terminal
+    if first_arg.as_deref() == Some("--sign") {
+        let Some(file) = args.next() else {
+            eprintln!("Usage: esc_terminal --sign <file> [scenario]");
+            std::process::exit(1);
+        };
+        if let Err(err) = payload::sign_file(Path::new(&file), &scenario_path(args.next())) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // The scenario file can be chosen as the first command line argument
+    macroquad::Window::from_config(window_conf(), run(scenario_path(first_arg)));
+}
+
+async fn run(scenario_path: PathBuf) {
+    let mut esc_os = match EscOS::new(&scenario_path).await {
+        Ok(esc_os) => esc_os,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
 
     loop {
-        esc_os.tick().await;
+        esc_os.tick();
 
         next_frame().await
     }
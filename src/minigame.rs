@@ -1,44 +1,24 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use macroquad::prelude::*;
 
 use crate::{
-    system::{texture_storage, BG_COLOR, FG_COLOR},
-    windows::{draw_outlined_box, Window, WindowReturnAction},
+    context::AppContext,
+    input::InputEvent,
+    scenario::MiniGameConfig,
+    session::{MiniGameProgress, WindowSnapshot, WindowState},
+    windows::{Window, WindowKind, WindowReturnAction},
 };
 
 const CELL_SIZE: f32 = 40.0;
-const NUM_OF_CELLS: usize = 20;
-const DURATION_BETWEEN_MOVES: Duration = Duration::from_millis(200);
+pub const NUM_OF_CELLS: usize = 20;
 
-const EMPTY_COLOR: Color = BG_COLOR;
 const PLAYER_COLOR: Color = GREEN;
 const OBSTACLE_COLOR: Color = BLACK;
 const PASSWORD_COLOR: Color = BLUE;
 const FINISH_COLOR: Color = GOLD;
-
-const MAP: &'static str = "
-OOOOOOOOOOOOOOOOOOOO
-O________X_________O
-O__OOOOOOOOOOOOO___O
-O______________O___O
-O______________O___O
-OOOOOOOOOOOOO__O_X_O
-O______________O___O
-O______________O___O
-O__OOOOOOOOOOOOO___O
-O__________________O
-O________X_________O
-O__________________O
-O____________O_____O
-OP___________O_X___O
-O____________O_____O
-O__________________O
-O__________________O
-O__________OOOOOOO_O
-O_________________XO
-OOOOOOOOOOOOOOOOOOOO";
-const KEYS_TOTAL: u8 = 5;
+const PAUSED_OVERLAY_COLOR: Color = Color::new(1.0, 1.0, 1.0, 0.8);
+const PAUSED_TEXT: &str = "CONNECTION LOST";
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 enum Cell {
@@ -49,76 +29,120 @@ enum Cell {
     Finish,
 }
 
-fn cells_from_string(string: &'static str) -> ([[Cell; NUM_OF_CELLS]; NUM_OF_CELLS], (i32, i32)) {
+fn cells_from_string(string: &str) -> ([[Cell; NUM_OF_CELLS]; NUM_OF_CELLS], (i32, i32)) {
     let mut cells = [[Cell::Empty; NUM_OF_CELLS]; NUM_OF_CELLS];
 
-    let mut row = 0;
-    let mut col = 0;
     let mut player_pos = (1, 9);
-    for char in string.chars().skip(1) {
-        match char.to_ascii_uppercase() {
-            '\n' => {
-                row += 1;
-                col = 0;
-                continue;
+    for (row, line) in string.trim().lines().take(NUM_OF_CELLS).enumerate() {
+        for (col, char) in line.trim().chars().take(NUM_OF_CELLS).enumerate() {
+            match char.to_ascii_uppercase() {
+                'O' => cells[row][col] = Cell::Obstacle,
+                'X' => cells[row][col] = Cell::PasswordPiece,
+                'P' => player_pos = (col as i32, row as i32),
+                _ => cells[row][col] = Cell::Empty,
             }
-            '_' => cells[row][col] = Cell::Empty,
-            'O' => cells[row][col] = Cell::Obstacle,
-            'X' => cells[row][col] = Cell::PasswordPiece,
-            'P' => player_pos = (col as i32, row as i32),
-            _ => continue,
         }
-        print!("{char}");
-
-        col += 1;
     }
 
     (cells, player_pos)
 }
 
 pub struct MiniGame {
+    config: MiniGameConfig,
     cells: [[Cell; NUM_OF_CELLS]; NUM_OF_CELLS],
     player_position: (i32, i32),
     player_movement: (i32, i32),
-    last_update: Instant,
+    /// Clock time of the last move, `None` until the first frame after (re)starting
+    last_update: Option<Duration>,
     new_movement: (i32, i32),
     keys_collected: u8,
     finish_reached: bool,
+    is_paused: bool,
+    /// Puzzle payload the game was started by
+    puzzle_id: Option<String>,
     top_left: Vec2,
     width: f32,
     height: f32,
 }
 
 impl MiniGame {
-    pub fn new() -> Self {
-        let (cells, player_position) = cells_from_string(MAP);
+    pub fn new(config: MiniGameConfig) -> Self {
+        let (cells, player_position) = cells_from_string(&config.map);
         MiniGame {
+            config,
             cells,
             player_position,
             player_movement: (1, 0),
-            last_update: Instant::now(),
+            last_update: None,
             new_movement: (1, 0),
             keys_collected: 0,
             finish_reached: false,
+            is_paused: false,
+            puzzle_id: None,
             top_left: vec2(200.0, 180.0),
             width: CELL_SIZE * NUM_OF_CELLS as f32 + 5.0,
             height: CELL_SIZE * NUM_OF_CELLS as f32 + 5.0,
         }
     }
 
-    pub fn restart(&mut self) {
-        *self = Self::new();
+    pub fn for_puzzle(config: MiniGameConfig, puzzle_id: String) -> Self {
+        MiniGame {
+            puzzle_id: Some(puzzle_id),
+            ..Self::new(config)
+        }
     }
-}
 
-impl Window for MiniGame {
-    async fn new_boxed() -> Box<dyn Window>
-    where
-        Self: Sized,
-    {
-        Box::new(Self::new())
+    /// Continues a game saved in a session, `config` is the one of its puzzle if it has one
+    pub fn from_progress(
+        config: MiniGameConfig,
+        progress: &MiniGameProgress,
+        top_left: Vec2,
+    ) -> Self {
+        let mut game = match &progress.puzzle {
+            Some(id) => Self::for_puzzle(config, id.clone()),
+            None => Self::new(config),
+        };
+        for &(col, row) in &progress.collected_pieces {
+            if let Some(cell) = game.cells.get_mut(row).and_then(|row| row.get_mut(col)) {
+                if *cell == Cell::PasswordPiece {
+                    *cell = Cell::Empty;
+                }
+            }
+        }
+
+        let (x, y) = progress.player_position;
+        if (0..NUM_OF_CELLS as i32).contains(&x) && (0..NUM_OF_CELLS as i32).contains(&y) {
+            game.player_position = progress.player_position;
+        }
+        game.player_movement = progress.player_movement;
+        game.new_movement = progress.player_movement;
+        game.keys_collected = progress.keys_collected.min(game.config.keys_total);
+        game.top_left = top_left;
+
+        game
     }
 
+    fn progress(&self) -> MiniGameProgress {
+        let (original_cells, _) = cells_from_string(&self.config.map);
+        let collected_pieces = (0..NUM_OF_CELLS)
+            .flat_map(|row| (0..NUM_OF_CELLS).map(move |col| (col, row)))
+            .filter(|&(col, row)| {
+                original_cells[row][col] == Cell::PasswordPiece
+                    && self.cells[row][col] != Cell::PasswordPiece
+            })
+            .collect();
+
+        MiniGameProgress {
+            player_position: self.player_position,
+            player_movement: self.player_movement,
+            keys_collected: self.keys_collected,
+            collected_pieces,
+            puzzle: self.puzzle_id.clone(),
+        }
+    }
+}
+
+impl Window for MiniGame {
     fn position(&self) -> Vec2 {
         self.top_left
     }
@@ -131,17 +155,15 @@ impl Window for MiniGame {
         Vec2::new(self.width, self.height)
     }
 
-    fn draw(&mut self) {
+    fn draw(&mut self, context: &AppContext) {
+        let theme = &context.theme;
         let width = self.width;
         let height = self.height;
-        draw_outlined_box(
-            self.top_left.x - 2.5,
-            self.top_left.y - 2.5,
-            width,
-            height,
+        context.renderer.draw_outlined_box(
+            Rect::new(self.top_left.x - 2.5, self.top_left.y - 2.5, width, height),
             5.0,
-            BG_COLOR,
-            FG_COLOR,
+            theme.background,
+            theme.foreground,
         );
 
         let mut row_pos = self.top_left().y;
@@ -150,17 +172,37 @@ impl Window for MiniGame {
             for cell in row {
                 let color = match cell {
                     Cell::Obstacle => OBSTACLE_COLOR,
-                    Cell::Empty => EMPTY_COLOR,
+                    Cell::Empty => theme.background,
                     Cell::Player => PLAYER_COLOR,
                     Cell::PasswordPiece => PASSWORD_COLOR,
                     Cell::Finish => FINISH_COLOR,
                 };
-                draw_rectangle(col_pos, row_pos, CELL_SIZE, CELL_SIZE, color);
+                context
+                    .renderer
+                    .draw_rectangle(col_pos, row_pos, CELL_SIZE, CELL_SIZE, color);
 
                 col_pos += CELL_SIZE;
             }
             row_pos += CELL_SIZE;
         }
+
+        if self.is_paused {
+            context.renderer.draw_rectangle(
+                self.top_left.x,
+                self.top_left.y,
+                width,
+                height,
+                PAUSED_OVERLAY_COLOR,
+            );
+            let dim = context.renderer.measure_text(PAUSED_TEXT, 1, 50.0);
+            context.renderer.draw_text(
+                PAUSED_TEXT,
+                self.top_left.x + width * 0.5 - dim.width * 0.5,
+                self.top_left.y + height * 0.5 + dim.offset_y * 0.5,
+                50.0,
+                theme.foreground,
+            );
+        }
     }
 
     fn is_visible(&self) -> bool {
@@ -169,30 +211,44 @@ impl Window for MiniGame {
 
     fn set_visibility(&mut self, _value: bool) {}
 
-    fn handle_input(&mut self, _event: crate::windows::InputEvent) -> WindowReturnAction {
-        let input = if is_key_pressed(KeyCode::Left) {
-            Some((-1, 0))
-        } else if is_key_pressed(KeyCode::Right) {
-            Some((1, 0))
-        } else if is_key_pressed(KeyCode::Up) {
-            Some((0, -1))
-        } else if is_key_pressed(KeyCode::Down) {
-            Some((0, 1))
-        } else {
-            None
+    fn handle_input(&mut self, _context: &AppContext, event: InputEvent) -> WindowReturnAction {
+        if self.is_paused {
+            return WindowReturnAction::None;
+        }
+
+        let InputEvent::KeyDown { key, .. } = event else {
+            return WindowReturnAction::None;
+        };
+        let input = match key {
+            KeyCode::Left => Some((-1, 0)),
+            KeyCode::Right => Some((1, 0)),
+            KeyCode::Up => Some((0, -1)),
+            KeyCode::Down => Some((0, 1)),
+            _ => None,
         };
         if let Some(input) = input {
             self.new_movement = input;
         }
 
+        WindowReturnAction::None
+    }
+
+    /// The player keeps moving while another window has the keyboard
+    fn update(&mut self, context: &AppContext) -> WindowReturnAction {
+        if self.is_paused {
+            return WindowReturnAction::None;
+        }
+
+        let now = context.clock.now();
+        let last_update = *self.last_update.get_or_insert(now);
         self.cells[self.player_position.1 as usize][self.player_position.0 as usize] = Cell::Empty;
-        if Instant::now().duration_since(self.last_update) > DURATION_BETWEEN_MOVES {
+        if now.saturating_sub(last_update) > self.config.move_interval() {
             self.player_movement = self.new_movement;
             self.player_position = (
                 self.player_position.0 + self.new_movement.0,
                 self.player_position.1 + self.new_movement.1,
             );
-            self.last_update = Instant::now();
+            self.last_update = Some(now);
 
             // Check validity of position
             if self.player_position.0 < 0
@@ -215,7 +271,10 @@ impl Window for MiniGame {
             if self.cells[self.player_position.1 as usize][self.player_position.0 as usize]
                 == Cell::Finish
             {
-                return WindowReturnAction::HackCompleted;
+                return match &self.puzzle_id {
+                    Some(id) => WindowReturnAction::PuzzleSolved(id.clone()),
+                    None => WindowReturnAction::HackCompleted,
+                };
             }
 
             if self.cells[self.player_position.1 as usize][self.player_position.0 as usize]
@@ -226,18 +285,59 @@ impl Window for MiniGame {
         }
         self.cells[self.player_position.1 as usize][self.player_position.0 as usize] = Cell::Player;
 
-        if self.keys_collected == KEYS_TOTAL {
-            self.cells[10][10] = Cell::Finish;
+        if self.keys_collected == self.config.keys_total {
+            let (col, row) = self.config.finish;
+            self.cells[row][col] = Cell::Finish;
         }
 
         WindowReturnAction::None
     }
 
-    fn icon(&self) -> Option<Texture2D> {
-        texture_storage().minigame()
+    fn icon(&self, context: &AppContext) -> Option<Texture2D> {
+        context.textures.minigame()
+    }
+
+    fn kind(&self) -> WindowKind {
+        WindowKind::MiniGame
+    }
+
+    fn title(&self) -> String {
+        "Minigame".to_string()
+    }
+
+    fn restart(&mut self) {
+        *self = MiniGame {
+            puzzle_id: self.puzzle_id.take(),
+            is_paused: self.is_paused,
+            ..Self::new(self.config.clone())
+        };
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        // Do not move right away after resuming
+        if self.is_paused && !paused {
+            self.last_update = None;
+        }
+        self.is_paused = paused;
+    }
+
+    fn snapshot(&self) -> Option<WindowSnapshot> {
+        Some(WindowSnapshot {
+            position: self.top_left.into(),
+            size: None,
+            is_visible: true,
+            state: WindowState::MiniGame(self.progress()),
+        })
     }
 
     fn contains_pos(&self, pos: Vec2) -> bool {
-        false
+        // Same box as the outline in `draw`
+        Rect::new(
+            self.top_left.x - 2.5,
+            self.top_left.y - 2.5,
+            self.width,
+            self.height,
+        )
+        .contains(pos)
     }
 }
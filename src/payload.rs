@@ -0,0 +1,166 @@
+use std::{fs, path::Path};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::scenario::{Scenario, UsbConfig};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Environment variable that overrides `usb.key_file`
+pub const KEY_ENV_VAR: &str = "ESC_HACK_KEY";
+const SIGNATURE_PREFIX: &str = "signature:";
+pub const ID_PREFIX: &str = "id:";
+
+/// Result of verifying a hack file from the USB drive
+pub enum Payload {
+    /// Signature matched, contains the normalized body without the signature line
+    Valid(String),
+    Invalid,
+}
+
+/// The key is only held by the terminal, either in the environment or in a local file
+pub fn load_key(config: &UsbConfig) -> Result<Vec<u8>, String> {
+    if let Ok(key) = std::env::var(KEY_ENV_VAR) {
+        if !key.trim().is_empty() {
+            return Ok(key.trim().as_bytes().to_vec());
+        }
+    }
+
+    let key = fs::read_to_string(&config.key_file).map_err(|err| {
+        format!(
+            "No signing key, set `{KEY_ENV_VAR}` or create `{}` ({err})",
+            config.key_file.display()
+        )
+    })?;
+    if key.trim().is_empty() {
+        return Err(format!("Key file `{}` is empty", config.key_file.display()));
+    }
+
+    Ok(key.trim().as_bytes().to_vec())
+}
+
+/// Splits the file into its normalized body and the hex signature. Line endings, indentation,
+/// trailing spaces and empty lines do not matter, so an editor save cannot break the payload.
+fn split_payload(content: &str) -> (String, Option<&str>) {
+    let mut signature = None;
+    let mut body_lines = vec![];
+    for line in content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        match line.strip_prefix(SIGNATURE_PREFIX) {
+            Some(hex) => signature = Some(hex.trim()),
+            None => body_lines.push(line),
+        }
+    }
+
+    (body_lines.join("\n"), signature)
+}
+
+fn mac(key: &[u8], body: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    mac
+}
+
+pub fn verify(content: &str, key: &[u8]) -> Payload {
+    let (body, signature) = split_payload(content);
+    let Some(signature) = signature.and_then(|hex| hex::decode(hex).ok()) else {
+        return Payload::Invalid;
+    };
+
+    match mac(key, &body).verify_slice(&signature) {
+        Ok(()) => Payload::Valid(body),
+        Err(_) => Payload::Invalid,
+    }
+}
+
+/// A payload may declare its id in an `id: <id>` line. Since the line is signed too,
+/// a renamed file cannot pose as a different payload.
+pub fn declared_id(body: &str) -> Option<&str> {
+    body.lines()
+        .find_map(|line| line.strip_prefix(ID_PREFIX))
+        .map(str::trim)
+}
+
+pub fn sign(content: &str, key: &[u8]) -> String {
+    let (body, _) = split_payload(content);
+    let signature = hex::encode(mac(key, &body).finalize().into_bytes());
+
+    format!("{body}\n{SIGNATURE_PREFIX} {signature}\n")
+}
+
+/// Signs the file in place with the key of the given scenario, used by `--sign`
+pub fn sign_file(file: &Path, scenario_path: &Path) -> Result<(), String> {
+    let scenario = Scenario::load(scenario_path).map_err(|err| err.to_string())?;
+    let key = load_key(&scenario.usb)?;
+
+    let content = fs::read_to_string(file)
+        .map_err(|err| format!("Failed to read `{}`: {err}", file.display()))?;
+    fs::write(file, sign(&content, &key))
+        .map_err(|err| format!("Failed to write `{}`: {err}", file.display()))?;
+
+    println!("Signed `{}`", file.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"test-key";
+    const BODY: &str = "id: hack\nprint(\"Connecting...\");\nstart_minigame();";
+
+    fn body(payload: Payload) -> Option<String> {
+        match payload {
+            Payload::Valid(body) => Some(body),
+            Payload::Invalid => None,
+        }
+    }
+
+    #[test]
+    fn signed_payload_is_valid() {
+        let signed = sign(BODY, KEY);
+        assert_eq!(body(verify(&signed, KEY)).as_deref(), Some(BODY));
+    }
+
+    #[test]
+    fn line_endings_and_whitespace_do_not_matter() {
+        let signed = sign(BODY, KEY);
+        let crlf = signed.replace('\n', "\r\n");
+        assert_eq!(body(verify(&crlf, KEY)).as_deref(), Some(BODY));
+
+        let reformatted = signed
+            .lines()
+            .map(|line| format!("    {line}  \t"))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        assert_eq!(body(verify(&reformatted, KEY)).as_deref(), Some(BODY));
+
+        // Signing a reformatted file gives the same signature
+        assert_eq!(sign(&format!("\r\n  {}\r\n", BODY), KEY), signed);
+    }
+
+    #[test]
+    fn tampered_payload_is_invalid() {
+        let signed = sign(BODY, KEY);
+        let tampered = signed.replace("Connecting", "Connected");
+        assert!(body(verify(&tampered, KEY)).is_none());
+        assert!(body(verify(&signed, b"other-key")).is_none());
+    }
+
+    #[test]
+    fn missing_or_malformed_signature_is_invalid() {
+        assert!(body(verify(BODY, KEY)).is_none());
+        let malformed = format!("{BODY}\n{SIGNATURE_PREFIX} not-hex\n");
+        assert!(body(verify(&malformed, KEY)).is_none());
+    }
+
+    #[test]
+    fn id_is_read_from_the_body() {
+        assert_eq!(declared_id(BODY), Some("hack"));
+        assert_eq!(declared_id("print(\"Hi\");"), None);
+    }
+}
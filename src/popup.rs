@@ -1,102 +1,81 @@
-use macroquad::{
-    prelude::*,
-    ui::{root_ui, widgets::Button},
-};
+use macroquad::prelude::*;
 
 use crate::{
-    system::{texture_storage, BG_COLOR, FG_COLOR, TEXTURE_STORAGE},
-    windows::{draw_outlined_box, InputEvent, Window, WindowReturnAction},
+    context::AppContext,
+    input::InputEvent,
+    session::{WindowSnapshot, WindowState},
+    windows::{Window, WindowKind, WindowReturnAction},
 };
 
 const WIDTH: f32 = 700.0;
 const HEIGHT: f32 = 200.0;
+const CLOSE_BUTTON_SIZE: f32 = 64.0;
 
 pub struct PopUp {
     position: Vec2,
     text: String,
-    close_button_clicked: bool,
 }
 
 impl PopUp {
-    pub fn new_with_text(text: String) -> Self {
+    pub fn new_with_text(context: &AppContext, text: String) -> Self {
         PopUp {
-            position: vec2(
-                screen_width() * 0.5 - WIDTH * 0.5,
-                screen_height() * 0.5 - HEIGHT * 0.5,
-            ),
+            position: Self::centered(context),
             text,
-            close_button_clicked: false,
         }
     }
-}
 
-impl Window for PopUp {
-    async fn new_boxed() -> Box<dyn Window>
-    where
-        Self: Sized,
-    {
-        Box::new(PopUp {
-            position: vec2(
-                screen_width() * 0.5 - WIDTH * 0.5,
-                screen_height() * 0.5 - HEIGHT * 0.5,
-            ),
-            text: "WARNING".to_string(),
-            close_button_clicked: false,
-        })
+    /// Top-left corner of a popup in the middle of the screen
+    fn centered(context: &AppContext) -> Vec2 {
+        (context.renderer.screen_size() - vec2(WIDTH, HEIGHT)) * 0.5
+    }
+
+    fn close_button_rect(&self) -> Rect {
+        let pos = self.top_left() + vec2(WIDTH - 50.0, 18.0);
+        Rect::new(pos.x, pos.y, CLOSE_BUTTON_SIZE, CLOSE_BUTTON_SIZE)
     }
+}
 
+impl Window for PopUp {
     fn position(&self) -> Vec2 {
         self.position
     }
 
     fn top_left(&self) -> Vec2 {
-        vec2(
-            screen_width() * 0.5 - WIDTH * 0.5,
-            screen_height() * 0.5 - HEIGHT * 0.5,
-        )
+        self.position
     }
 
     fn size(&self) -> Vec2 {
         Vec2::new(WIDTH, HEIGHT)
     }
 
-    fn draw(&mut self) {
+    fn draw(&mut self, context: &AppContext) {
+        let theme = &context.theme;
         let tl = self.top_left();
 
-        draw_outlined_box(tl.x, tl.y, WIDTH, HEIGHT, 5.0, BG_COLOR, FG_COLOR);
+        context.renderer.draw_outlined_box(
+            Rect::new(tl.x, tl.y, WIDTH, HEIGHT),
+            5.0,
+            theme.background,
+            theme.foreground,
+        );
 
-        let dim = measure_text(self.text.as_str(), None, 1, 40.0);
-        draw_multiline_text(
+        let dim = context.renderer.measure_text(self.text.as_str(), 1, 40.0);
+        context.renderer.draw_multiline_text(
             self.text.as_str(),
             self.position.x + 50.0,
             self.position.y + 1.8 * dim.offset_y,
             40.0,
             None,
-            FG_COLOR,
+            theme.foreground,
         );
 
         // Draw close button
-        let icon = texture_storage().close().unwrap();
-        let button_style = root_ui()
-            .style_builder()
-            .color(BLANK)
-            .color_hovered(BLANK)
-            .color_clicked(BLANK)
-            .text_color(BLANK)
-            .text_color_hovered(BLANK)
-            .build();
-        let mut skin = root_ui().default_skin();
-        skin.button_style = button_style;
-        root_ui().push_skin(&skin);
-
-        let pos = self.top_left() + vec2(WIDTH - 50.0, 18.0);
-        draw_texture(&icon, pos.x, pos.y, BG_COLOR);
-        self.close_button_clicked = Button::new("")
-            .size(Vec2::new(64.0, 64.0))
-            .position(pos)
-            .ui(&mut root_ui());
-
-        root_ui().pop_skin();
+        if let Some(icon) = context.textures.close() {
+            let pos = self.close_button_rect().point();
+            context
+                .renderer
+                .draw_texture(&icon, pos.x, pos.y, theme.background);
+        }
     }
 
     fn is_visible(&self) -> bool {
@@ -105,21 +84,44 @@ impl Window for PopUp {
 
     fn set_visibility(&mut self, _value: bool) {}
 
-    fn handle_input(&mut self, event: InputEvent) -> WindowReturnAction {
+    /// Stays in the middle when the screen size changes
+    fn update(&mut self, context: &AppContext) -> WindowReturnAction {
+        self.position = Self::centered(context);
+        WindowReturnAction::None
+    }
+
+    fn handle_input(&mut self, _context: &AppContext, event: InputEvent) -> WindowReturnAction {
         match event {
-            InputEvent::LeftMouse(_pos, _) => {
-                if self.close_button_clicked {
-                    WindowReturnAction::Close
-                } else {
-                    WindowReturnAction::None
-                }
-            }
+            InputEvent::MouseDown {
+                button: MouseButton::Left,
+                pos,
+                ..
+            } if self.close_button_rect().contains(pos) => WindowReturnAction::Close,
             _ => WindowReturnAction::None,
         }
     }
 
-    fn icon(&self) -> Option<Texture2D> {
-        texture_storage().popup()
+    fn icon(&self, context: &AppContext) -> Option<Texture2D> {
+        context.textures.popup()
+    }
+
+    fn kind(&self) -> WindowKind {
+        WindowKind::PopUp
+    }
+
+    fn title(&self) -> String {
+        self.text.clone()
+    }
+
+    fn snapshot(&self) -> Option<WindowSnapshot> {
+        Some(WindowSnapshot {
+            position: self.position.into(),
+            size: None,
+            is_visible: true,
+            state: WindowState::PopUp {
+                text: self.text.clone(),
+            },
+        })
     }
 
     fn contains_pos(&self, pos: Vec2) -> bool {
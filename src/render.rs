@@ -0,0 +1,126 @@
+use macroquad::prelude::*;
+
+/// Everything the windows draw goes through here, so the room can also run without a screen
+pub trait Renderer {
+    fn screen_size(&self) -> Vec2;
+
+    /// Documents are textures, they can only be opened if the renderer can create them
+    fn supports_textures(&self) -> bool {
+        true
+    }
+
+    fn clear_background(&self, color: Color);
+
+    fn draw_rectangle(&self, x: f32, y: f32, width: f32, height: f32, color: Color);
+
+    fn draw_rectangle_lines(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        thickness: f32,
+        color: Color,
+    );
+
+    fn draw_circle(&self, x: f32, y: f32, radius: f32, color: Color);
+
+    fn draw_text(&self, text: &str, x: f32, y: f32, font_size: f32, color: Color);
+
+    fn draw_multiline_text(
+        &self,
+        text: &str,
+        x: f32,
+        y: f32,
+        font_size: f32,
+        line_distance_factor: Option<f32>,
+        color: Color,
+    );
+
+    fn draw_texture(&self, texture: &Texture2D, x: f32, y: f32, color: Color);
+
+    fn draw_texture_ex(
+        &self,
+        texture: &Texture2D,
+        x: f32,
+        y: f32,
+        color: Color,
+        params: DrawTextureParams,
+    );
+
+    /// Always with the default font
+    fn measure_text(&self, text: &str, font_size: u16, font_scale: f32) -> TextDimensions;
+
+    fn draw_outlined_box(&self, rect: Rect, thickness: f32, background: Color, outline: Color) {
+        self.draw_rectangle(rect.x, rect.y, rect.w, rect.h, background);
+        self.draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, thickness, outline);
+    }
+}
+
+/// Draws to the macroquad window
+pub struct MacroquadRenderer;
+
+impl Renderer for MacroquadRenderer {
+    fn screen_size(&self) -> Vec2 {
+        vec2(screen_width(), screen_height())
+    }
+
+    fn clear_background(&self, color: Color) {
+        clear_background(color);
+    }
+
+    fn draw_rectangle(&self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        draw_rectangle(x, y, width, height, color);
+    }
+
+    fn draw_rectangle_lines(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        thickness: f32,
+        color: Color,
+    ) {
+        draw_rectangle_lines(x, y, width, height, thickness, color);
+    }
+
+    fn draw_circle(&self, x: f32, y: f32, radius: f32, color: Color) {
+        draw_circle(x, y, radius, color);
+    }
+
+    fn draw_text(&self, text: &str, x: f32, y: f32, font_size: f32, color: Color) {
+        draw_text(text, x, y, font_size, color);
+    }
+
+    fn draw_multiline_text(
+        &self,
+        text: &str,
+        x: f32,
+        y: f32,
+        font_size: f32,
+        line_distance_factor: Option<f32>,
+        color: Color,
+    ) {
+        draw_multiline_text(text, x, y, font_size, line_distance_factor, color);
+    }
+
+    fn draw_texture(&self, texture: &Texture2D, x: f32, y: f32, color: Color) {
+        draw_texture(texture, x, y, color);
+    }
+
+    fn draw_texture_ex(
+        &self,
+        texture: &Texture2D,
+        x: f32,
+        y: f32,
+        color: Color,
+        params: DrawTextureParams,
+    ) {
+        draw_texture_ex(texture, x, y, color, params);
+    }
+
+    fn measure_text(&self, text: &str, font_size: u16, font_scale: f32) -> TextDimensions {
+        measure_text(text, None, font_size, font_scale)
+    }
+}
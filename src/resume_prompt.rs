@@ -0,0 +1,153 @@
+use macroquad::prelude::*;
+
+use crate::{
+    context::AppContext,
+    input::InputEvent,
+    session::WindowSnapshot,
+    windows::{Window, WindowKind, WindowReturnAction},
+};
+
+const WIDTH: f32 = 700.0;
+const HEIGHT: f32 = 260.0;
+const BUTTON_SIZE: Vec2 = Vec2::new(200.0, 50.0);
+const TEXT: &str = "A previous game was interrupted.\nDo you want to continue it?";
+
+/// Shown on startup when an unfinished session was found on disk
+pub struct ResumePrompt {
+    /// Center of the screen
+    position: Vec2,
+}
+
+impl ResumePrompt {
+    pub fn new(context: &AppContext) -> Self {
+        ResumePrompt {
+            position: context.renderer.screen_size() * 0.5,
+        }
+    }
+
+    /// The continue and the new game button
+    fn button_rects(&self) -> (Rect, Rect) {
+        let tl = self.top_left();
+        let y = tl.y + HEIGHT - BUTTON_SIZE.y - 40.0;
+        (
+            Rect::new(tl.x + 80.0, y, BUTTON_SIZE.x, BUTTON_SIZE.y),
+            Rect::new(
+                tl.x + WIDTH - 80.0 - BUTTON_SIZE.x,
+                y,
+                BUTTON_SIZE.x,
+                BUTTON_SIZE.y,
+            ),
+        )
+    }
+
+    fn draw_button(context: &AppContext, label: &str, rect: Rect) {
+        let theme = &context.theme;
+        let bigger = BUTTON_SIZE * 1.05;
+        let box_position = rect.point() - (bigger - BUTTON_SIZE) * 0.5;
+        context.renderer.draw_outlined_box(
+            Rect::new(box_position.x, box_position.y, bigger.x, bigger.y),
+            5.0,
+            theme.foreground,
+            theme.foreground,
+        );
+
+        let dim = context.renderer.measure_text(label, 30, 1.0);
+        context.renderer.draw_text(
+            label,
+            rect.center().x - dim.width * 0.5,
+            rect.center().y + dim.offset_y * 0.5,
+            30.0,
+            theme.background,
+        );
+    }
+}
+
+impl Window for ResumePrompt {
+    fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    fn top_left(&self) -> Vec2 {
+        self.position() - vec2(WIDTH, HEIGHT) * 0.5
+    }
+
+    fn size(&self) -> Vec2 {
+        Vec2::new(WIDTH, HEIGHT)
+    }
+
+    fn draw(&mut self, context: &AppContext) {
+        let theme = &context.theme;
+        let tl = self.top_left();
+        context.renderer.draw_outlined_box(
+            Rect::new(tl.x, tl.y, WIDTH, HEIGHT),
+            5.0,
+            theme.background,
+            theme.foreground,
+        );
+
+        let dim = context.renderer.measure_text(TEXT, 1, 40.0);
+        context.renderer.draw_multiline_text(
+            TEXT,
+            tl.x + 50.0,
+            tl.y + 1.8 * dim.offset_y,
+            40.0,
+            None,
+            theme.foreground,
+        );
+
+        let (resume, new_game) = self.button_rects();
+        Self::draw_button(context, "Continue", resume);
+        Self::draw_button(context, "New game", new_game);
+    }
+
+    fn is_visible(&self) -> bool {
+        true
+    }
+
+    fn set_visibility(&mut self, _value: bool) {}
+
+    fn update(&mut self, context: &AppContext) -> WindowReturnAction {
+        self.position = context.renderer.screen_size() * 0.5;
+        WindowReturnAction::None
+    }
+
+    fn handle_input(&mut self, _context: &AppContext, event: InputEvent) -> WindowReturnAction {
+        let (resume, new_game) = self.button_rects();
+        match event {
+            InputEvent::MouseDown {
+                button: MouseButton::Left,
+                pos,
+                ..
+            } if resume.contains(pos) => WindowReturnAction::ResumeSession,
+            InputEvent::MouseDown {
+                button: MouseButton::Left,
+                pos,
+                ..
+            } if new_game.contains(pos) => WindowReturnAction::Close,
+            _ => WindowReturnAction::None,
+        }
+    }
+
+    fn icon(&self, context: &AppContext) -> Option<Texture2D> {
+        context.textures.popup()
+    }
+
+    fn kind(&self) -> WindowKind {
+        WindowKind::ResumePrompt
+    }
+
+    fn title(&self) -> String {
+        "Resume previous game?".to_string()
+    }
+
+    fn snapshot(&self) -> Option<WindowSnapshot> {
+        None
+    }
+
+    fn contains_pos(&self, pos: Vec2) -> bool {
+        let tl = self.top_left();
+        let br = tl + vec2(WIDTH, HEIGHT);
+
+        pos.x >= tl.x && pos.x <= br.x && pos.y >= tl.y && pos.y <= br.y
+    }
+}
@@ -0,0 +1,491 @@
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{minigame::NUM_OF_CELLS, vfs::VirtualFs};
+
+pub const DEFAULT_SCENARIO_PATH: &str = "scenario.toml";
+
+/// Everything that defines a single room variant. Loaded from a TOML file so game masters
+/// can build new variants without recompiling. Every section is optional and falls back
+/// to the values of the original room.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub usb: UsbConfig,
+    pub hack: HackConfig,
+    pub login: LoginConfig,
+    pub minigame: MiniGameConfig,
+    pub control: ControlConfig,
+    pub session: SessionConfig,
+    pub terminal: TerminalConfig,
+    pub payloads: Vec<PayloadConfig>,
+    /// Virtual filesystem the terminal can explore
+    pub files: Vec<FileConfig>,
+    /// Documents that open scrambled until they are decrypted
+    pub encrypted: Vec<EncryptedDocument>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsbConfig {
+    /// Only volumes mounted under these directories are scanned, all of them if empty.
+    /// `ESC_USB_PATH` is added to the list if set.
+    pub mount_roots: Vec<PathBuf>,
+    /// Run and supervise `udiskie` for automounting
+    pub udiskie: bool,
+    /// Key the hack file signature is verified with, overridden by `ESC_HACK_KEY`
+    pub key_file: PathBuf,
+}
+
+/// A file on a USB drive and what it does when it is plugged in. Each payload fires only once
+/// per game.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PayloadConfig {
+    pub id: String,
+    /// Name of the file that is looked for in the root of every newly mounted volume
+    pub file: String,
+    pub action: PayloadAction,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PayloadAction {
+    /// The regular hack sequence: hack script, minigame, unlock
+    Hack,
+    /// Skips straight to the unlocked system, meant for game-master sticks
+    Unlock,
+    Message {
+        text: String,
+    },
+    /// Opens a set of documents
+    Documents {
+        documents: Vec<String>,
+    },
+    /// Starts a minigame with its own map. Solving it completes the payload, and with
+    /// `unlocks = true` unlocks the system like the hack.
+    Puzzle {
+        minigame: MiniGameConfig,
+        #[serde(default)]
+        unlocks: bool,
+    },
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HackConfig {
+    pub completed_text: String,
+    /// Shown when a hack file with a wrong or missing signature is inserted
+    pub invalid_payload_text: String,
+    /// What happens when the USB drive is pulled out before the hack is completed
+    pub on_usb_removed: UsbRemovedBehavior,
+    pub connection_lost_text: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsbRemovedBehavior {
+    /// Cancel the hack, it has to be started from the beginning
+    Abort,
+    /// Freeze the hack until the same drive is plugged in again
+    Pause,
+    /// Keep going as if nothing happened
+    Ignore,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoginConfig {
+    pub error_text: String,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MiniGameConfig {
+    /// `O` obstacle, `_` empty, `X` password piece, `P` player start
+    pub map: String,
+    pub keys_total: u8,
+    pub move_interval_ms: u64,
+    /// Cell (column, row) where the finish appears once all keys are collected
+    pub finish: (usize, usize),
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    pub enabled: bool,
+    /// Address of the game-master control server, use `0.0.0.0:<port>` to expose it on the LAN
+    pub bind: String,
+    /// If set, every request has to carry it in the `X-Control-Token` header or `token` query
+    pub token: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerminalConfig {
+    /// The terminal opens together with the document list once the hack is completed
+    pub enabled: bool,
+    /// Shown in the prompt and by `whoami`
+    pub user: String,
+    pub hostname: String,
+    /// Starting directory, `~` in paths
+    pub home: String,
+}
+
+/// An entry of the virtual filesystem
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// Absolute path, parent folders are created automatically
+    pub path: String,
+    #[serde(rename = "type", default)]
+    pub kind: FileKind,
+    /// Text of a `text` file
+    #[serde(default)]
+    pub content: String,
+    /// Name of the image in `assets/documents` without the extension, for `document` files
+    pub document: Option<String>,
+    /// Defaults to `terminal.user`
+    pub owner: Option<String>,
+    /// Only listed by `ls -a`, hidden documents are not in the document list
+    #[serde(default)]
+    pub hidden: bool,
+    /// Locking a folder locks everything inside it
+    pub lock: Option<FileLock>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    #[default]
+    Text,
+    Document,
+    Folder,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FileLock {
+    /// Opened with `decrypt <path> <password>` in the terminal
+    Password { password: String },
+    /// Opens once the payload was used, puzzles have to be solved as well
+    Payload { payload: String },
+    /// Opens the given time after the system was unlocked
+    Time { after_secs: u64 },
+}
+
+/// Decrypted with the key typed into the document window, by a payload or by the game master
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptedDocument {
+    /// Name of the document in `assets/documents`
+    pub document: String,
+    pub key: Option<String>,
+    /// Decrypts the document once the payload was used, puzzles have to be solved as well
+    pub payload: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    pub enabled: bool,
+    pub path: PathBuf,
+    pub save_interval_ms: u64,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            usb: UsbConfig::default(),
+            hack: HackConfig::default(),
+            login: LoginConfig::default(),
+            minigame: MiniGameConfig::default(),
+            control: ControlConfig::default(),
+            session: SessionConfig::default(),
+            terminal: TerminalConfig::default(),
+            payloads: vec![PayloadConfig {
+                id: "hack".to_string(),
+                file: "secret.hack".to_string(),
+                action: PayloadAction::Hack,
+            }],
+            files: vec![],
+            encrypted: vec![],
+        }
+    }
+}
+
+impl Default for UsbConfig {
+    fn default() -> Self {
+        UsbConfig {
+            key_file: PathBuf::from("hack.key"),
+            mount_roots: vec![
+                PathBuf::from("/media"),
+                PathBuf::from("/run/media"),
+                PathBuf::from("/mnt"),
+            ],
+            udiskie: true,
+        }
+    }
+}
+
+impl Default for HackConfig {
+    fn default() -> Self {
+        HackConfig {
+            completed_text: "Hack completed!".to_string(),
+            invalid_payload_text: "Invalid payload!\nAccess denied.".to_string(),
+            on_usb_removed: UsbRemovedBehavior::Abort,
+            connection_lost_text: "Connection lost!\nHack aborted.".to_string(),
+        }
+    }
+}
+
+impl Default for LoginConfig {
+    fn default() -> Self {
+        LoginConfig {
+            error_text: "Error:\nLogin is disabled during emergency\nprotocol!".to_string(),
+        }
+    }
+}
+
+impl Default for MiniGameConfig {
+    fn default() -> Self {
+        MiniGameConfig {
+            map: "
+OOOOOOOOOOOOOOOOOOOO
+O________X_________O
+O__OOOOOOOOOOOOO___O
+O______________O___O
+O______________O___O
+OOOOOOOOOOOOO__O_X_O
+O______________O___O
+O______________O___O
+O__OOOOOOOOOOOOO___O
+O__________________O
+O________X_________O
+O__________________O
+O____________O_____O
+OP___________O_X___O
+O____________O_____O
+O__________________O
+O__________________O
+O__________OOOOOOO_O
+O_________________XO
+OOOOOOOOOOOOOOOOOOOO"
+                .to_string(),
+            keys_total: 5,
+            move_interval_ms: 200,
+            finish: (10, 10),
+        }
+    }
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        ControlConfig {
+            enabled: true,
+            bind: "127.0.0.1:8080".to_string(),
+            token: None,
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            enabled: true,
+            path: PathBuf::from("session.json"),
+            save_interval_ms: 5000,
+        }
+    }
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        TerminalConfig {
+            enabled: true,
+            user: "agent".to_string(),
+            hostname: "esc".to_string(),
+            home: "/home/agent".to_string(),
+        }
+    }
+}
+
+impl SessionConfig {
+    pub fn save_interval(&self) -> Duration {
+        Duration::from_millis(self.save_interval_ms)
+    }
+}
+
+impl MiniGameConfig {
+    pub fn move_interval(&self) -> Duration {
+        Duration::from_millis(self.move_interval_ms)
+    }
+
+    /// Rows of the map without the surrounding whitespace
+    pub fn rows(&self) -> impl Iterator<Item = &str> {
+        self.map.trim().lines().map(str::trim)
+    }
+}
+
+pub enum ScenarioError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(path, err) => {
+                write!(f, "Failed to read scenario `{}`: {err}", path.display())
+            }
+            ScenarioError::Parse(path, err) => {
+                write!(f, "Failed to parse scenario `{}`:\n{err}", path.display())
+            }
+            ScenarioError::Invalid(path, msg) => {
+                write!(f, "Invalid scenario `{}`: {msg}", path.display())
+            }
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let content =
+            fs::read_to_string(path).map_err(|err| ScenarioError::Io(path.to_path_buf(), err))?;
+        let scenario: Scenario = toml::from_str(&content)
+            .map_err(|err| ScenarioError::Parse(path.to_path_buf(), err))?;
+        scenario
+            .validate()
+            .map_err(|msg| ScenarioError::Invalid(path.to_path_buf(), msg))?;
+
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut ids = HashSet::new();
+        for payload in &self.payloads {
+            if !ids.insert(payload.id.as_str()) {
+                return Err(format!(
+                    "payload id `{}` is used more than once",
+                    payload.id
+                ));
+            }
+            if payload.file.is_empty() || payload.file.contains(['/', '\\']) {
+                return Err(format!(
+                    "file of payload `{}` must be a plain file name, got `{}`",
+                    payload.id, payload.file
+                ));
+            }
+            if let PayloadAction::Puzzle { minigame, .. } = &payload.action {
+                minigame
+                    .validate()
+                    .map_err(|msg| format!("payload `{}`: {msg}", payload.id))?;
+            }
+        }
+
+        if self.control.enabled && self.control.bind.parse::<SocketAddr>().is_err() {
+            return Err(format!(
+                "control.bind `{}` is not a valid socket address",
+                self.control.bind
+            ));
+        }
+
+        if self.session.enabled && self.session.save_interval_ms == 0 {
+            return Err("session.save_interval_ms must be greater than 0".to_string());
+        }
+
+        VirtualFs::new(&self.files, &self.encrypted, &self.terminal)
+            .map_err(|msg| format!("files: {msg}"))?;
+        for file in &self.files {
+            if let Some(FileLock::Payload { payload }) = &file.lock {
+                if !ids.contains(payload.as_str()) {
+                    return Err(format!(
+                        "file `{}` is locked by unknown payload `{payload}`",
+                        file.path
+                    ));
+                }
+            }
+        }
+
+        for encrypted in &self.encrypted {
+            if let Some(payload) = &encrypted.payload {
+                if !ids.contains(payload.as_str()) {
+                    return Err(format!(
+                        "document `{}` is decrypted by unknown payload `{payload}`",
+                        encrypted.document
+                    ));
+                }
+            }
+        }
+
+        self.minigame.validate()
+    }
+}
+
+impl MiniGameConfig {
+    fn validate(&self) -> Result<(), String> {
+        let rows = self.rows().collect::<Vec<_>>();
+        if rows.len() != NUM_OF_CELLS {
+            return Err(format!(
+                "minigame.map must have {NUM_OF_CELLS} rows, found {}",
+                rows.len()
+            ));
+        }
+
+        let mut players = 0;
+        let mut pieces = 0;
+        for (row_index, row) in rows.iter().enumerate() {
+            if row.chars().count() != NUM_OF_CELLS {
+                return Err(format!(
+                    "minigame.map row {} must have {NUM_OF_CELLS} cells, found {}",
+                    row_index + 1,
+                    row.chars().count()
+                ));
+            }
+            for char in row.chars() {
+                match char.to_ascii_uppercase() {
+                    'O' | '_' => {}
+                    'X' => pieces += 1,
+                    'P' => players += 1,
+                    other => {
+                        return Err(format!(
+                            "minigame.map row {} contains unknown cell `{other}`",
+                            row_index + 1
+                        ))
+                    }
+                }
+            }
+        }
+
+        if players != 1 {
+            return Err(format!(
+                "minigame.map must contain exactly one player `P`, found {players}"
+            ));
+        }
+        if self.keys_total == 0 || self.keys_total as usize > pieces {
+            return Err(format!(
+                "minigame.keys_total must be between 1 and the {pieces} password pieces on the map"
+            ));
+        }
+        if self.move_interval_ms == 0 {
+            return Err("minigame.move_interval_ms must be greater than 0".to_string());
+        }
+
+        let (col, row) = self.finish;
+        match rows.get(row).and_then(|row| row.chars().nth(col)) {
+            None => Err(format!(
+                "minigame.finish ({col}, {row}) is outside of the map"
+            )),
+            Some('O' | 'o') => Err(format!("minigame.finish ({col}, {row}) is an obstacle")),
+            Some(_) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use macroquad::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context::AppContext,
+    document::DocumentWindow,
+    document_list::DocumentList,
+    minigame::MiniGame,
+    popup::PopUp,
+    scenario::PayloadAction,
+    terminal::TerminalWindow,
+    vfs::{FilesystemState, VirtualFs},
+    windows::Window,
+};
+
+/// Snapshot of a running game, written periodically so a crash or power loss
+/// does not cost the players their progress
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub hack_status: SavedHackStatus,
+    pub is_unlocked: bool,
+    #[serde(default)]
+    pub fired_payloads: Vec<String>,
+    /// Volume and id of the payload that started the running hack
+    #[serde(default)]
+    pub active_hack: Option<(PathBuf, String)>,
+    #[serde(default)]
+    pub hack_paused: bool,
+    #[serde(default)]
+    pub filesystem: FilesystemState,
+    /// Bottom to top
+    pub windows: Vec<WindowSnapshot>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SavedHackStatus {
+    NoUSB,
+    USBOpened,
+    Minigame,
+    Completed,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WindowSnapshot {
+    /// Center of the window
+    pub position: (f32, f32),
+    /// `None` for windows with a fixed size
+    #[serde(default)]
+    pub size: Option<(f32, f32)>,
+    pub is_visible: bool,
+    pub state: WindowState,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum WindowState {
+    PopUp {
+        text: String,
+    },
+    MiniGame(MiniGameProgress),
+    Document {
+        name: String,
+        #[serde(flatten)]
+        view: DocumentView,
+    },
+    DocumentList,
+    Terminal {
+        cwd: String,
+        scrollback: Vec<String>,
+    },
+}
+
+/// Page and scroll positions of a document window
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DocumentView {
+    /// Of the current page
+    pub vertical_offset: f32,
+    #[serde(default)]
+    pub page: usize,
+    /// Of every page, the current one included
+    #[serde(default)]
+    pub page_offsets: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MiniGameProgress {
+    pub player_position: (i32, i32),
+    pub player_movement: (i32, i32),
+    pub keys_collected: u8,
+    /// Cells (column, row) of password pieces that were already picked up
+    pub collected_pieces: Vec<(usize, usize)>,
+    /// Id of the puzzle payload, `None` for the minigame of the hack
+    #[serde(default)]
+    pub puzzle: Option<String>,
+}
+
+impl Session {
+    /// Returns `Ok(None)` if there is no session file
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Writes to a temporary file first so a power loss never leaves a half-written session
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(tmp_path, path)
+    }
+
+    pub fn delete(path: &Path) -> io::Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the players got far enough for resuming to make sense
+    pub fn has_progress(&self) -> bool {
+        self.is_unlocked || self.hack_status != SavedHackStatus::NoUSB
+    }
+}
+
+impl WindowSnapshot {
+    pub fn restore(
+        &self,
+        context: &AppContext,
+        filesystem: &Rc<VirtualFs>,
+    ) -> Option<Box<dyn Window>> {
+        let position = Vec2::from(self.position);
+        // Set before the position, which is the center of the window
+        let size = self.size.map(Vec2::from);
+        let mut window: Box<dyn Window> = match &self.state {
+            WindowState::PopUp { text } => Box::new(PopUp::new_with_text(context, text.clone())),
+            WindowState::MiniGame(progress) => {
+                let config =
+                    match &progress.puzzle {
+                        // The puzzle may have been removed from the scenario since
+                        Some(id) => context.config.payloads.iter().find_map(|payload| {
+                            match &payload.action {
+                                PayloadAction::Puzzle { minigame, .. } if payload.id == *id => {
+                                    Some(minigame.clone())
+                                }
+                                _ => None,
+                            }
+                        })?,
+                        None => context.config.minigame.clone(),
+                    };
+                Box::new(MiniGame::from_progress(config, progress, position))
+            }
+            WindowState::Document { name, view } => {
+                // The document may have been removed since the snapshot was taken
+                if !context.textures.has_document(name) {
+                    return None;
+                }
+                DocumentWindow::restore_boxed(
+                    context,
+                    name.clone(),
+                    filesystem.clone(),
+                    position,
+                    size,
+                    view,
+                )
+            }
+            WindowState::DocumentList => {
+                DocumentList::new_boxed_at(position, size, filesystem.clone())
+            }
+            WindowState::Terminal { cwd, scrollback } => TerminalWindow::restore_boxed(
+                context,
+                filesystem.clone(),
+                position,
+                size,
+                cwd.clone(),
+                scrollback.clone(),
+            ),
+        };
+        window.set_visibility(self.is_visible);
+
+        Some(window)
+    }
+}
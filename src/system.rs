@@ -1,50 +1,83 @@
-use std::collections::{HashMap, LinkedList};
-use std::path::PathBuf;
-use std::process::{Child, Command};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::time::Instant;
-use std::{fs, mem};
+use std::time::Duration;
 
-use chrono::{Local, Timelike};
+use chrono::Timelike;
 use macroquad::prelude::*;
-use macroquad::ui::root_ui;
-use macroquad::ui::widgets::Button;
 
+use crate::context::{AppContext, Clock};
+use crate::control::{ControlCommand, ControlServer, StatusReport, WindowReport};
 use crate::document::DocumentWindow;
 use crate::document_list::DocumentList;
+use crate::document_meta::{DocumentLink, DocumentMeta};
+use crate::hack_console::HackConsole;
+use crate::hack_script;
+use crate::input::{InputCollector, InputEvent, InputSource};
 use crate::login::LoginWindow;
 use crate::minigame::MiniGame;
+use crate::payload::{self, Payload};
 use crate::popup::PopUp;
-use crate::windows::{draw_outlined_box, InputEvent, Window, WindowReturnAction};
+use crate::render::{MacroquadRenderer, Renderer};
+use crate::resume_prompt::ResumePrompt;
+use crate::scenario::{PayloadAction, PayloadConfig, Scenario, ScenarioError, UsbRemovedBehavior};
+use crate::session::{SavedHackStatus, Session};
+use crate::terminal::TerminalWindow;
+use crate::text_document;
+use crate::usb::{Udiskie, UsbEvent, UsbWatcher};
+use crate::vfs::VirtualFs;
+use crate::window_manager::{WindowId, WindowManager};
+use crate::windows::{Window, WindowKind, WindowReturnAction};
 
 pub const BG_COLOR: Color = WHITE;
 pub const FG_COLOR: Color = BLACK;
 
-const BAR_COLOR: Color = FG_COLOR;
-const BAR_TEXT_COLOR: Color = BG_COLOR;
-
-const TOP_BAR_HEIGHT: f32 = 50.0;
+pub const TOP_BAR_HEIGHT: f32 = 50.0;
 const BAR_FONT_SIZE: (u16, f32) = (1, 40.0);
 
 const DOCK_ICON_SIZE: u32 = 64;
 const DOCK_SPACING: u32 = 16;
+const DOCK_HOVER_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.2);
 
-pub static mut LAST_MOUSE_POS: Vec2 = Vec2::new(0.0, 0.0);
-pub static mut TEXTURE_STORAGE: Option<Rc<TextureStorage>> = None;
+/// Page of a document. The size is kept next to the texture because measuring a texture
+/// needs a graphics context, which a headless simulation does not have.
+#[derive(Clone)]
+pub struct Page {
+    /// `None` in a headless simulation
+    pub texture: Option<Texture2D>,
+    pub size: Vec2,
+}
 
-const HACK_FILE_NAME: &str = "secret.hack";
+impl Page {
+    pub fn new(texture: Texture2D) -> Self {
+        Page {
+            size: texture.size(),
+            texture: Some(texture),
+        }
+    }
 
+    /// Page without a texture, for a headless simulation
+    pub fn blank(size: Vec2) -> Self {
+        Page {
+            texture: None,
+            size,
+        }
+    }
+}
+
+/// Icons and documents, an empty storage draws windows without them
+#[derive(Default)]
 pub struct TextureStorage {
     document_icon: Option<Texture2D>,
     minimize_icon: Option<Texture2D>,
     popup_icon: Option<Texture2D>,
     close_icon: Option<Texture2D>,
     minigame_icon: Option<Texture2D>,
-    documents: HashMap<String, Texture2D>,
-}
-
-pub fn texture_storage() -> Rc<TextureStorage> {
-    unsafe { TEXTURE_STORAGE.clone().unwrap() }
+    logo: Option<Texture2D>,
+    /// Pages of every document
+    documents: HashMap<String, Vec<Page>>,
+    document_meta: HashMap<String, DocumentMeta>,
 }
 
 impl TextureStorage {
@@ -68,331 +101,1207 @@ impl TextureStorage {
         self.minigame_icon.clone()
     }
 
+    pub fn logo(&self) -> Option<Texture2D> {
+        self.logo.clone()
+    }
+
+    /// First page of the document, `None` in a headless simulation
     pub fn document_by_name(&self, name: &str) -> Option<Texture2D> {
+        self.documents.get(name)?.first()?.texture.clone()
+    }
+
+    pub fn has_document(&self, name: &str) -> bool {
+        self.documents.contains_key(name)
+    }
+
+    pub fn document_pages(&self, name: &str) -> Option<Vec<Page>> {
         self.documents.get(name).cloned()
     }
 
-    pub fn fallback_document(&self) -> Texture2D {
-        todo!()
+    /// Replaces a document of the same name
+    pub fn add_document(&mut self, name: String, pages: Vec<Page>, meta: Option<DocumentMeta>) {
+        if let Some(meta) = meta {
+            self.document_meta.insert(name.clone(), meta);
+        } else {
+            self.document_meta.remove(&name);
+        }
+        self.documents.insert(name, pages);
+    }
+
+    /// Sidecar of the document, empty if it has none
+    pub fn document_meta(&self, name: &str) -> DocumentMeta {
+        self.document_meta.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Title from the sidecar, the name if there is none
+    pub fn document_title(&self, name: &str) -> String {
+        self.document_meta
+            .get(name)
+            .and_then(|meta| meta.title.clone())
+            .unwrap_or(name.to_string())
+    }
+
+    pub fn has_redaction(&self, id: &str) -> bool {
+        self.document_meta
+            .values()
+            .flat_map(|meta| &meta.redactions)
+            .any(|redaction| redaction.id == id)
+    }
+
+    /// Sorted by name
+    pub fn document_names(&self) -> Vec<String> {
+        let mut names = self.documents.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Placeholder for a document that does not exist, e.g. because of a typo in the scenario
+    pub fn fallback_document(&self, name: &str) -> Texture2D {
+        // Garbage that looks like a hex dump, the same for every name
+        let mut seed = name.bytes().fold(0x811C9DC5_u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+        let dump = (0..16)
+            .map(|line| {
+                let bytes = (0..12)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                        match seed >> 24 {
+                            0..=20 => "??".to_string(),
+                            byte => format!("{byte:02x}"),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                format!("{:08x}  {}", line * 12, bytes.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        text_document::render_markdown(&format!(
+            "# FILE CORRUPTED\n\n\
+             **{name}** could not be read.\n\n\
+             The file was not found or its contents are damaged beyond recovery.\n\n\
+             ```\n{dump}\n```\n\n\
+             ---\n\n\
+             ERROR 0x0000DEAD: SECTOR READ FAILURE\n"
+        ))
     }
 }
 
 #[derive(Clone, Copy, PartialEq)]
 enum HackStatus {
     NoUSB,
-    USBOpened(Instant),
+    /// The hack script is running in the console
+    USBOpened,
     Minigame,
     Completed,
 }
 
+impl HackStatus {
+    fn save(&self) -> SavedHackStatus {
+        match self {
+            HackStatus::NoUSB => SavedHackStatus::NoUSB,
+            HackStatus::USBOpened => SavedHackStatus::USBOpened,
+            HackStatus::Minigame => SavedHackStatus::Minigame,
+            HackStatus::Completed => SavedHackStatus::Completed,
+        }
+    }
+
+    fn restore(saved: SavedHackStatus) -> Self {
+        match saved {
+            SavedHackStatus::NoUSB => HackStatus::NoUSB,
+            SavedHackStatus::USBOpened => HackStatus::USBOpened,
+            SavedHackStatus::Minigame => HackStatus::Minigame,
+            SavedHackStatus::Completed => HackStatus::Completed,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            HackStatus::NoUSB => "NoUSB",
+            HackStatus::USBOpened => "USBOpened",
+            HackStatus::Minigame => "Minigame",
+            HackStatus::Completed => "Completed",
+        }
+    }
+}
+
+/// Window that gets an input event
+#[derive(Clone, Copy, PartialEq)]
+enum InputTarget {
+    Window(WindowId),
+    /// Not part of the window manager, below all other windows
+    Login,
+}
+
+/// What the room runs on, the macroquad window or a headless simulation
+pub struct Backend {
+    pub renderer: Box<dyn Renderer>,
+    pub input: Box<dyn InputSource>,
+    pub usb_watcher: UsbWatcher,
+    pub clock: Clock,
+}
+
 pub struct EscOS {
-    logo_texture: Texture2D,
+    context: AppContext,
     login_window: Box<dyn Window>,
-    windows: Vec<Box<dyn Window>>,
+    windows: WindowManager,
     is_unlocked: bool,
 
-    hack_file_content: String,
-    last_usb_check: Instant,
-    usb_path: PathBuf,
-    usb_detected_at: Option<Instant>,
+    input: Box<dyn InputSource>,
+    /// Window a mouse button was pressed on, it gets the drags until the button is released
+    pointer_capture: Option<InputTarget>,
+    /// Window every finger started on, by touch id
+    touch_capture: HashMap<u64, InputTarget>,
+
+    filesystem: Rc<VirtualFs>,
+    hack_key: Vec<u8>,
+    usb_watcher: UsbWatcher,
+    /// Mounted volumes that carry a valid payload
+    payload_volumes: HashSet<PathBuf>,
+    /// Ids of payloads that already fired this game
+    fired_payloads: HashSet<String>,
+    /// False after a reset until the USB drive is removed, so a stick left in the port
+    /// does not start the hack again right away
+    usb_armed: bool,
+    /// Volume and id of the payload that started the running hack
+    active_hack: Option<(PathBuf, String)>,
+    /// The drive of the running hack was removed with `on_usb_removed = "pause"`
+    hack_paused: bool,
     hack_status: HackStatus,
 
-    udiskie: Child,
+    udiskie: Option<Udiskie>,
+    control: Option<ControlServer>,
+
+    /// Clock time of the last save
+    last_session_save: Duration,
+    /// Session found on startup, waiting for the players to decide whether to resume it
+    pending_session: Option<Session>,
+}
+
+/// Text documents are rendered here and can be searched, so their links and source are
+/// returned too
+async fn load_pages(path: &Path) -> (Vec<Texture2D>, Vec<DocumentLink>, Option<String>) {
+    let is_text = path
+        .extension()
+        .is_some_and(|ext| ext == "txt" || ext == "md");
+    if is_text {
+        let (pages, links, source) = text_document::load(path)
+            .await
+            .expect("Failed to load text document.");
+        (pages, links, Some(source))
+    } else {
+        let texture = load_texture(&path.to_string_lossy())
+            .await
+            .expect("Failed to load texture.");
+        (vec![texture], vec![], None)
+    }
 }
 
-async fn load_texture_storage() {
+async fn load_texture_storage() -> TextureStorage {
     // Get document names
     let names = fs::read_dir("./assets/documents/").expect("Failed to read directory entries.");
+    let mut storage = TextureStorage {
+        document_icon: load_texture("assets/document_icon.png").await.ok(),
+        minimize_icon: load_texture("assets/minimize.png").await.ok(),
+        popup_icon: load_texture("assets/warning.png").await.ok(),
+        close_icon: load_texture("assets/close.png").await.ok(),
+        minigame_icon: load_texture("assets/minigame.png").await.ok(),
+        logo: load_texture("assets/logo.png").await.ok(),
+        ..Default::default()
+    };
     // Load each texture
-    let mut documents = HashMap::new();
-    for future in names.flatten().map(async |entry| {
+    // Sidecars are loaded together with their document
+    let entries = names
+        .flatten()
+        .filter(|entry| entry.path().extension().is_none_or(|ext| ext != "toml"));
+    for future in entries.map(async |entry| {
+        let path = entry.path();
         let name = entry
             .file_name()
             .into_string()
             .expect("Failed to parse OsString to String.");
-        let texture = load_texture(format!("assets/documents/{name}").as_str())
-            .await
-            .expect("Failed to load texture.");
         let name = name
             .split(".")
             .nth(0)
             .expect("Invalid filename.")
             .to_string();
 
-        (name, texture)
+        // A folder is a document with one page per file, sorted by name
+        if !path.is_dir() {
+            let (pages, links, source) = load_pages(&path).await;
+            return (name, pages, links, source);
+        }
+        let mut files = fs::read_dir(&path)
+            .expect("Failed to read directory entries.")
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut pages = vec![];
+        let mut links = vec![];
+        let mut sources = vec![];
+        for file in files {
+            let (file_pages, file_links, source) = load_pages(&file).await;
+            // Link pages count from the first page of the file
+            links.extend(file_links.into_iter().map(|link| DocumentLink {
+                page: link.page + pages.len(),
+                ..link
+            }));
+            pages.extend(file_pages);
+            sources.extend(source);
+        }
+        let source = (!sources.is_empty()).then(|| sources.join("\n"));
+
+        (name, pages, links, source)
     }) {
-        let (name, texture) = future.await;
+        let (name, pages, links, source) = future.await;
+        if pages.is_empty() {
+            eprintln!("Document `{name}` has no pages.");
+            continue;
+        }
 
         // Print debug info
         println!("Load document: {name}");
 
-        documents.insert(name, texture);
+        let sidecar = PathBuf::from(format!("assets/documents/{name}.toml"));
+        let mut meta = match DocumentMeta::load(&sidecar) {
+            Ok(meta) => meta,
+            Err(err) => {
+                eprintln!("{err}");
+                None
+            }
+        };
+        if let Some(source) = source {
+            let meta = meta.get_or_insert_with(DocumentMeta::default);
+            if meta.title.is_none() {
+                meta.title = text_document::title(&source);
+            }
+            if meta.body.is_empty() {
+                meta.body = source;
+            }
+        }
+        if !links.is_empty() {
+            meta.get_or_insert_with(DocumentMeta::default)
+                .links
+                .extend(links);
+        }
+        storage.add_document(name, pages.into_iter().map(Page::new).collect(), meta);
     }
 
-    unsafe {
-        TEXTURE_STORAGE = Some(Rc::new(TextureStorage {
-            document_icon: load_texture("assets/document_icon.png").await.ok(),
-            minimize_icon: load_texture("assets/minimize.png").await.ok(),
-            popup_icon: load_texture("assets/warning.png").await.ok(),
-            close_icon: load_texture("assets/close.png").await.ok(),
-            minigame_icon: load_texture("assets/minigame.png").await.ok(),
-            documents,
-        }));
-    }
+    storage
 }
 
 impl EscOS {
-    pub async fn new() -> Self {
-        // Spawn udiskie for automounting
-        let udiskie = Command::new("udiskie")
-            .arg("-a")
-            .spawn()
-            .expect("Failed to spawn udiskie!");
-
-        // Load Texture storage
-        load_texture_storage().await;
-        let usb_path =
-            std::env::var("ESC_USB_PATH").expect("The env variable `ESC_USB_PATH` was not set!");
-
-        EscOS {
-            logo_texture: load_texture("assets/logo.png").await.unwrap(),
-            login_window: LoginWindow::new_boxed().await,
-            windows: vec![],
+    pub async fn new(scenario_path: &Path) -> Result<Self, ScenarioError> {
+        let scenario = Scenario::load(scenario_path)?;
+        let mut mount_roots = scenario.usb.mount_roots.clone();
+        if let Ok(usb_path) = std::env::var("ESC_USB_PATH") {
+            mount_roots.push(PathBuf::from(usb_path));
+        }
+        let backend = Backend {
+            renderer: Box::new(MacroquadRenderer),
+            input: Box::new(InputCollector::new()),
+            usb_watcher: UsbWatcher::start(mount_roots),
+            clock: Clock::new(),
+        };
+
+        Self::with_backend(
+            scenario_path,
+            scenario,
+            load_texture_storage().await,
+            backend,
+        )
+    }
+
+    /// `scenario_path` is only used in error messages
+    pub fn with_backend(
+        scenario_path: &Path,
+        scenario: Scenario,
+        textures: TextureStorage,
+        backend: Backend,
+    ) -> Result<Self, ScenarioError> {
+        let hack_key = payload::load_key(&scenario.usb)
+            .map_err(|msg| ScenarioError::Invalid(scenario_path.to_path_buf(), msg))?;
+
+        let control = if scenario.control.enabled {
+            match ControlServer::start(&scenario.control.bind, scenario.control.token.clone()) {
+                Ok(server) => Some(server),
+                Err(err) => {
+                    eprintln!("Failed to start control server: {err}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let pending_session = if scenario.session.enabled {
+            match Session::load(&scenario.session.path) {
+                Ok(session) => session.filter(Session::has_progress),
+                Err(err) => {
+                    eprintln!("Failed to load session: {err}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // udiskie is started by the first tick
+        let udiskie = scenario.usb.udiskie.then(Udiskie::new);
+
+        let mut filesystem =
+            VirtualFs::new(&scenario.files, &scenario.encrypted, &scenario.terminal)
+                .expect("the filesystem is validated when the scenario is loaded");
+        let document_names = textures.document_names();
+        for file in filesystem.documents() {
+            let name = file.document.as_deref().unwrap_or_default();
+            if !document_names.iter().any(|known| known == name) {
+                eprintln!("Document `{name}` of `{}` does not exist", file.path);
+            }
+        }
+        for name in filesystem.encrypted_documents() {
+            if !document_names.contains(&name) {
+                eprintln!("Encrypted document `{name}` does not exist");
+            }
+        }
+        for name in &document_names {
+            let pages = textures.document_pages(name).unwrap_or_default();
+            let meta = textures.document_meta(name);
+            for link in &meta.links {
+                if !document_names.contains(&link.target) {
+                    eprintln!("`{name}` links to `{}`, which does not exist", link.target);
+                }
+            }
+            for redaction in meta.redactions {
+                if redaction.page == 0 || redaction.page > pages.len() {
+                    eprintln!(
+                        "Redaction `{}` of `{name}` is on page {}, which does not exist",
+                        redaction.id, redaction.page
+                    );
+                }
+                let payload = redaction.payload.as_deref();
+                if payload.is_some_and(|id| !scenario.payloads.iter().any(|p| p.id == id)) {
+                    eprintln!(
+                        "Redaction `{}` of `{name}` is revealed by an unknown payload",
+                        redaction.id
+                    );
+                }
+            }
+        }
+        filesystem.add_unplaced_documents(
+            &document_names,
+            &format!("{}/documents", scenario.terminal.home),
+            &scenario.terminal.user,
+        );
+
+        let context = AppContext::new(backend.renderer, textures, backend.clock, scenario);
+        let mut windows = WindowManager::new();
+        if pending_session.is_some() {
+            windows.push(Box::new(ResumePrompt::new(&context)));
+        }
+
+        Ok(EscOS {
+            login_window: Box::new(LoginWindow::new(&context, &context.config.login)),
+            windows,
             is_unlocked: false,
 
-            hack_file_content: fs::read_to_string(HACK_FILE_NAME).unwrap(),
-            last_usb_check: Instant::now(),
-            usb_path: PathBuf::from(usb_path),
-            usb_detected_at: None,
+            input: backend.input,
+            pointer_capture: None,
+            touch_capture: HashMap::new(),
+
+            filesystem: Rc::new(filesystem),
+            hack_key,
+            usb_watcher: backend.usb_watcher,
+            payload_volumes: HashSet::new(),
+            fired_payloads: HashSet::new(),
+            usb_armed: true,
+            active_hack: None,
+            hack_paused: false,
             hack_status: HackStatus::NoUSB,
 
             udiskie,
-        }
+            control,
+            last_session_save: context.clock.now(),
+            pending_session,
+            context,
+        })
     }
 
-    pub async fn tick(&mut self) {
-        // Check hack file
-        if self.check_hack_file() && self.hack_status == HackStatus::NoUSB {
-            self.hack_status = HackStatus::USBOpened(Instant::now());
-            self.windows.push(Box::new(PopUp::new_with_text(
-                "Hack in progress!".to_string(),
-            )));
+    pub fn tick(&mut self) {
+        self.context.clock.tick();
+
+        // Commands from the game master
+        let commands = match &self.control {
+            Some(control) => control.poll_commands(),
+            None => vec![],
+        };
+        for command in commands {
+            self.handle_control_command(command);
         }
 
-        if let HackStatus::USBOpened(instant) = self.hack_status {
-            if Instant::now().duration_since(instant).as_secs() > 2 {
-                self.windows.push(Box::new(MiniGame::new()));
-                self.hack_status = HackStatus::Minigame;
+        if let Some(message) = self.udiskie.as_mut().and_then(Udiskie::supervise) {
+            self.log_event(message);
+        }
+
+        // Check newly mounted volumes for the hack file
+        for event in self.usb_watcher.poll_events() {
+            match event {
+                UsbEvent::Mounted(volume) => self.on_volume_mounted(volume),
+                UsbEvent::Removed(volume) => self.on_volume_removed(volume),
             }
         }
 
-        // DEBUG
-        if is_key_pressed(KeyCode::Home) {
-            self.on_hack_completed().await;
+        // Windows that run on their own, like the hack console and the minigame
+        for win_id in self.windows.ids().into_iter().rev() {
+            let Some(window) = self.windows.get_mut(win_id) else {
+                continue;
+            };
+            if !window.is_visible() {
+                continue;
+            }
+            let action = window.update(&self.context);
+            self.handle_action(win_id, action);
         }
 
+        for event in self.input.poll() {
+            self.route_event(event);
+        }
+        self.context.input = self.input.state();
+
         self.draw_background();
 
-        let mut windows_to_close = LinkedList::new();
+        // If the system is locked, draw only login window and not dock
+        if !self.is_unlocked {
+            self.login_window.update(&self.context);
+            // Typing goes to the password field while no other window is open
+            let has_focus = self.windows.focused().is_none();
+            self.login_window.set_focused(has_focus);
+            self.login_window.draw(&self.context);
+        }
 
-        let mouse_pos = vec2(mouse_position().0, mouse_position().1);
-        let wheel_scroll = mouse_wheel().1;
-        let mut event = {
-            if is_mouse_button_pressed(MouseButton::Left) {
-                InputEvent::LeftMouse(mouse_pos, false)
-            } else if is_mouse_button_down(MouseButton::Left) {
-                InputEvent::LeftMouse(mouse_pos, true)
-            } else if wheel_scroll != 0.0 {
-                InputEvent::Scroll(wheel_scroll)
-            } else {
-                InputEvent::None
+        for win in self.windows.iter_mut() {
+            if win.is_visible() {
+                win.draw(&self.context);
+            }
+        }
+
+        self.draw_dock();
+        self.draw_top_bar();
+
+        if let Some(control) = &self.control {
+            control.publish_status(self.status_report());
+        }
+
+        // Do not overwrite a session the players may still want to resume
+        if self.context.config.session.enabled
+            && self.pending_session.is_none()
+            && self
+                .context
+                .clock
+                .now()
+                .saturating_sub(self.last_session_save)
+                > self.context.config.session.save_interval()
+        {
+            self.save_session();
+        }
+    }
+
+    /// Pointer events go to the window under the pointer or the one holding it, keyboard
+    /// events to the focused window
+    fn route_event(&mut self, event: InputEvent) {
+        let target = match event {
+            // DEBUG
+            InputEvent::KeyDown {
+                key: KeyCode::Home,
+                repeat: false,
+                ..
+            } => {
+                self.force_unlock();
+                return;
+            }
+            // GM reset: Ctrl + Shift + R
+            InputEvent::KeyDown {
+                key: KeyCode::R,
+                modifiers,
+                repeat: false,
+            } if modifiers.ctrl && modifiers.shift => {
+                self.log_event("GM: reset from keyboard".to_string());
+                self.reset();
+                return;
+            }
+            InputEvent::MouseDown { button, pos, .. } => {
+                // The dock is drawn over the windows
+                if button == MouseButton::Left && self.press_dock(pos) {
+                    return;
+                }
+                let target = self.target_at(pos);
+                // Click to focus, the window comes to the front before it gets the click
+                if let Some(InputTarget::Window(id)) = target {
+                    self.windows.raise(id);
+                }
+                self.pointer_capture = target;
+                target
+            }
+            InputEvent::Drag { .. } | InputEvent::DoubleClick { .. } => self.pointer_capture,
+            InputEvent::MouseUp { .. } => self.pointer_capture.take(),
+            InputEvent::MouseMove { pos, .. } | InputEvent::Wheel { pos, .. } => {
+                self.target_at(pos)
+            }
+            InputEvent::TouchStart { id, pos } => {
+                let target = self.target_at(pos);
+                if let Some(target) = target {
+                    self.touch_capture.insert(id, target);
+                }
+                target
+            }
+            InputEvent::TouchMove { id, .. } => self.touch_capture.get(&id).copied(),
+            InputEvent::TouchEnd { id, .. } => self.touch_capture.remove(&id),
+            InputEvent::KeyDown { .. } | InputEvent::KeyUp { .. } | InputEvent::Text(_) => {
+                match self.windows.focused() {
+                    Some(id) => Some(InputTarget::Window(id)),
+                    None if !self.is_unlocked => Some(InputTarget::Login),
+                    None => None,
+                }
             }
         };
 
-        for index in (0..self.windows.len()).rev() {
-            if !self.windows[index].is_visible() {
-                continue;
+        match target {
+            Some(InputTarget::Window(id)) => {
+                let Some(window) = self.windows.get_mut(id) else {
+                    return;
+                };
+                let action = window.handle_input(&self.context, event);
+                self.handle_action(id, action);
+            }
+            Some(InputTarget::Login) => {
+                if let WindowReturnAction::NewWindow(new_win) =
+                    self.login_window.handle_input(&self.context, event)
+                {
+                    self.windows.push(new_win);
+                }
             }
+            None => {}
+        }
+    }
 
-            let this_event = if self.windows[index].contains_pos(mouse_pos) {
-                mem::replace(&mut event, InputEvent::None)
-            } else {
-                InputEvent::None
-            };
-            match self.windows[index].handle_input(this_event) {
-                WindowReturnAction::None => {}
-                WindowReturnAction::Minimize => self.windows[index].set_visibility(false),
-                WindowReturnAction::Close => windows_to_close.push_front(index),
-                WindowReturnAction::NewWindow(new_win) => self.windows.push(new_win),
-                WindowReturnAction::HackCompleted => {
-                    windows_to_close.push_front(0);
-                    windows_to_close.push_front(index);
-                    self.on_hack_completed().await;
+    /// Topmost window under the position, the login window is below all others
+    fn target_at(&self, pos: Vec2) -> Option<InputTarget> {
+        if let Some(id) = self.windows.window_at(pos) {
+            return Some(InputTarget::Window(id));
+        }
+        if !self.is_unlocked && self.login_window.contains_pos(pos) {
+            return Some(InputTarget::Login);
+        }
+        None
+    }
+
+    fn handle_action(&mut self, win_id: WindowId, action: WindowReturnAction) {
+        match action {
+            WindowReturnAction::None => {}
+            WindowReturnAction::Minimize => self.windows.hide(win_id),
+            WindowReturnAction::Close => {
+                if let Some(window) = self.windows.remove(win_id) {
+                    if window.kind() == WindowKind::ResumePrompt {
+                        self.discard_session();
+                    }
+                }
+            }
+            WindowReturnAction::NewWindow(new_win) => {
+                self.windows.push(new_win);
+            }
+            WindowReturnAction::HackCompleted => {
+                self.windows.remove(win_id);
+                // The hack console is closed together with the minigame
+                self.windows
+                    .retain(|win| win.kind() != WindowKind::HackConsole);
+                if !self.is_unlocked {
+                    self.on_hack_completed();
+                }
+            }
+            WindowReturnAction::PuzzleSolved(id) => {
+                self.windows.remove(win_id);
+                self.log_event(format!("Puzzle `{id}` solved"));
+                self.filesystem.complete_payload(&id);
+                let unlocks = self.context.config.payloads.iter().any(|config| {
+                    config.id == id
+                        && matches!(config.action, PayloadAction::Puzzle { unlocks: true, .. })
+                });
+                if unlocks {
+                    self.force_unlock();
+                }
+            }
+            WindowReturnAction::ResumeSession => self.resume_session(),
+            WindowReturnAction::OpenDocument(name) => self.open_document(name),
+            WindowReturnAction::DecryptAttempt(name, true) => {
+                self.log_event(format!("Document `{name}` decrypted"));
+            }
+            WindowReturnAction::DecryptAttempt(name, false) => {
+                self.log_event(format!("Wrong key for document `{name}`"));
+            }
+            WindowReturnAction::StartMiniGame => self.start_minigame(),
+            WindowReturnAction::FollowLink(target, new_window) => {
+                // A document that is open already is brought to the front instead
+                let open = self
+                    .windows
+                    .iter()
+                    .find(|(_, win)| win.document_name() == Some(target.as_str()))
+                    .map(|(id, _)| id);
+                if let Some(id) = open {
+                    self.windows.raise(id);
+                } else if new_window {
+                    self.open_document(target);
+                } else if let Some(window) = self.windows.get_mut(win_id) {
+                    window.navigate_to(&self.context, target);
                 }
             }
         }
-        for index in windows_to_close {
-            self.windows.remove(index);
+    }
+
+    fn save_session(&mut self) {
+        self.last_session_save = self.context.clock.now();
+        let session = Session {
+            hack_status: self.hack_status.save(),
+            is_unlocked: self.is_unlocked,
+            fired_payloads: self.fired_payloads.iter().cloned().collect(),
+            active_hack: self.active_hack.clone(),
+            hack_paused: self.hack_paused,
+            filesystem: self.filesystem.save_state(self.context.clock.now()),
+            windows: self
+                .windows
+                .iter()
+                .filter_map(|(_, win)| win.snapshot())
+                .collect(),
+        };
+        if let Err(err) = session.save(&self.context.config.session.path) {
+            self.log_event(format!("Failed to save session: {err}"));
         }
+    }
 
-        // If the system is locked, draw only login window and not dock
-        if !self.is_unlocked {
-            let this_event = if self.login_window.contains_pos(mouse_pos) {
-                mem::replace(&mut event, InputEvent::None)
-            } else {
-                InputEvent::None
-            };
-            match self.login_window.handle_input(this_event) {
-                WindowReturnAction::NewWindow(new_win) => self.windows.push(new_win),
-                _ => {}
+    fn resume_session(&mut self) {
+        let Some(session) = self.pending_session.take() else {
+            return;
+        };
+
+        self.windows = session
+            .windows
+            .iter()
+            .filter_map(|snapshot| snapshot.restore(&self.context, &self.filesystem))
+            .collect();
+        // The new windows reuse the ids of the old ones
+        self.pointer_capture = None;
+        self.touch_capture.clear();
+        self.is_unlocked = session.is_unlocked;
+        self.hack_status = HackStatus::restore(session.hack_status);
+        self.fired_payloads = session.fired_payloads.into_iter().collect();
+        self.active_hack = session.active_hack;
+        self.hack_paused = session.hack_paused;
+        if self.hack_paused {
+            for win in self.windows.iter_mut() {
+                win.set_paused(true);
             }
-            self.login_window.draw();
         }
+        self.filesystem
+            .restore_state(session.filesystem, self.context.clock.now());
+        self.log_event("Session resumed".to_string());
 
-        for win in &mut self.windows {
-            if win.is_visible() {
-                win.draw();
+        // The hack script is not saved, skip straight to the minigame
+        if self.hack_status == HackStatus::USBOpened {
+            self.start_minigame();
+        }
+    }
+
+    /// Forgets the saved session, the next save starts a fresh one
+    fn discard_session(&mut self) {
+        self.pending_session = None;
+        // Without sessions the file may belong to something else
+        if !self.context.config.session.enabled {
+            return;
+        }
+        if let Err(err) = Session::delete(&self.context.config.session.path) {
+            self.log_event(format!("Failed to delete session: {err}"));
+        }
+    }
+
+    /// Commands of the game master, usually from the control server
+    pub fn handle_control_command(&mut self, command: ControlCommand) {
+        match command {
+            ControlCommand::Unlock => {
+                self.log_event("GM: force unlock".to_string());
+                self.force_unlock();
+            }
+            ControlCommand::Reset => {
+                self.log_event("GM: reset".to_string());
+                self.reset();
+            }
+            ControlCommand::PopUp(text) => {
+                self.log_event(format!("GM: popup `{text}`"));
+                self.windows
+                    .push(Box::new(PopUp::new_with_text(&self.context, text)));
+            }
+            ControlCommand::OpenDocument(name) => {
+                self.log_event(format!("GM: open document `{name}`"));
+                self.open_document(name);
+            }
+            ControlCommand::Decrypt(name) => {
+                if self.filesystem.force_decrypt(&name) {
+                    self.log_event(format!("GM: decrypt `{name}`"));
+                } else {
+                    self.log_event(format!("GM: `{name}` is not an encrypted document"));
+                }
+            }
+            ControlCommand::Reveal(id) => {
+                if self.context.textures.has_redaction(&id) {
+                    self.filesystem.reveal(&id);
+                    self.log_event(format!("GM: reveal `{id}`"));
+                } else {
+                    self.log_event(format!("GM: no document has the redaction `{id}`"));
+                }
+            }
+            ControlCommand::RestartMiniGame => {
+                let mut restarted = false;
+                for win in self.windows.iter_mut() {
+                    if win.kind() == WindowKind::MiniGame {
+                        win.restart();
+                        restarted = true;
+                    }
+                }
+                if restarted {
+                    self.log_event("GM: restart minigame".to_string());
+                } else {
+                    self.log_event("GM: no minigame is running".to_string());
+                }
             }
         }
+    }
 
-        self.draw_dock();
-        self.draw_top_bar();
+    /// Skips whatever is left of the hack
+    fn force_unlock(&mut self) {
+        if !self.is_unlocked {
+            self.windows.retain(|win| {
+                !matches!(win.kind(), WindowKind::MiniGame | WindowKind::HackConsole)
+            });
+            self.on_hack_completed();
+        }
+    }
+
+    fn start_minigame(&mut self) {
+        if self.hack_status != HackStatus::USBOpened {
+            return;
+        }
+
+        self.windows.push(Box::new(MiniGame::new(
+            self.context.config.minigame.clone(),
+        )));
+        self.hack_status = HackStatus::Minigame;
+        self.log_event("Minigame started".to_string());
+    }
 
-        unsafe {
-            let pos = mouse_position();
-            LAST_MOUSE_POS = Vec2::new(pos.0, pos.1);
+    /// A missing document still opens, showing a placeholder
+    fn open_document(&mut self, name: String) {
+        if !self.context.textures.has_document(&name) {
+            self.log_event(format!("Unknown document `{name}`, showing a placeholder"));
         }
+        let window = DocumentWindow::new_boxed(&self.context, name, self.filesystem.clone());
+        self.windows.push(window);
     }
 
-    async fn on_hack_completed(&mut self) {
+    /// Brings the room back to the login screen for the next group without restarting
+    /// the process (and udiskie with it)
+    fn reset(&mut self) {
+        self.discard_session();
+        self.windows.clear();
+        self.pointer_capture = None;
+        self.touch_capture.clear();
+        self.login_window = Box::new(LoginWindow::new(&self.context, &self.context.config.login));
+        self.is_unlocked = false;
+
+        self.hack_status = HackStatus::NoUSB;
+        self.fired_payloads.clear();
+        self.filesystem.reset();
+        self.usb_armed = self.payload_volumes.is_empty();
+        self.active_hack = None;
+        self.hack_paused = false;
+    }
+
+    /// Moves a fake clock, see `Clock::fake`
+    pub fn advance_clock(&mut self, duration: Duration) {
+        self.context.clock.advance(duration);
+    }
+
+    pub fn filesystem(&self) -> &VirtualFs {
+        &self.filesystem
+    }
+
+    pub fn status_report(&self) -> StatusReport {
+        StatusReport {
+            hack_status: self.hack_status.name().to_string(),
+            is_unlocked: self.is_unlocked,
+            windows: self
+                .windows
+                .iter()
+                .map(|(id, win)| WindowReport {
+                    id,
+                    kind: win.kind(),
+                    title: win.title(),
+                    top_left: win.top_left().into(),
+                    size: win.size().into(),
+                    is_visible: win.is_visible(),
+                    is_focused: self.windows.focused() == Some(id),
+                })
+                .collect(),
+        }
+    }
+
+    /// Prints the event and forwards it to the game master
+    fn log_event(&self, message: String) {
+        println!("{message}");
+        if let Some(control) = &self.control {
+            control.push_event(message);
+        }
+    }
+
+    fn on_hack_completed(&mut self) {
+        self.log_event("Hack completed".to_string());
         self.hack_status = HackStatus::Completed;
         self.is_unlocked = true;
+        if let Some((_, id)) = self.active_hack.take() {
+            self.filesystem.complete_payload(&id);
+        }
+        self.hack_paused = false;
+        self.filesystem.on_system_unlocked(self.context.clock.now());
 
         // Open document list
-        self.windows.push(DocumentList::new_boxed().await);
+        self.windows.push(DocumentList::new_boxed(
+            &self.context,
+            self.filesystem.clone(),
+        ));
+        if self.context.config.terminal.enabled {
+            self.windows.push(Box::new(TerminalWindow::new(
+                &self.context,
+                self.context.config.terminal.clone(),
+                self.filesystem.clone(),
+            )));
+        }
 
         self.windows.push(Box::new(PopUp::new_with_text(
-            "Hack completed!".to_string(),
+            &self.context,
+            self.context.config.hack.completed_text.clone(),
         )));
     }
 
     fn draw_background(&self) {
-        clear_background(BG_COLOR);
+        let theme = self.context.theme;
+        self.context.renderer.clear_background(theme.background);
 
         // Draw logo
-        let x = screen_width() * 0.5 - self.logo_texture.width() * 0.5;
-        let y = screen_height() * 0.5 - self.logo_texture.height() * 0.5;
-        draw_texture(&self.logo_texture, x, y, BG_COLOR);
+        if let Some(logo) = self.context.textures.logo() {
+            let position = (self.context.renderer.screen_size() - logo.size()) * 0.5;
+            self.context
+                .renderer
+                .draw_texture(&logo, position.x, position.y, theme.background);
+        }
     }
 
     fn draw_top_bar(&self) {
-        draw_rectangle(0.0, 0.0, screen_width(), TOP_BAR_HEIGHT, BAR_COLOR);
-        let cur_time = Local::now();
+        let theme = self.context.theme;
+        self.context.renderer.draw_rectangle(
+            0.0,
+            0.0,
+            self.context.renderer.screen_size().x,
+            TOP_BAR_HEIGHT,
+            theme.foreground,
+        );
+        let cur_time = self.context.clock.time_of_day();
         let time_text = format!(
             "{:<02}:{:<02}:{:<02}",
             cur_time.hour(),
             cur_time.minute(),
             cur_time.second()
         );
-        let dim = measure_text(time_text.as_str(), None, BAR_FONT_SIZE.0, BAR_FONT_SIZE.1);
-        draw_text(
+        let dim = self.context.renderer.measure_text(
+            time_text.as_str(),
+            BAR_FONT_SIZE.0,
+            BAR_FONT_SIZE.1,
+        );
+        self.context.renderer.draw_text(
             time_text.as_str(),
-            screen_width() * 0.5 - dim.width * 0.5,
+            self.context.renderer.screen_size().x * 0.5 - dim.width * 0.5,
             TOP_BAR_HEIGHT * 0.5 - dim.height * 0.5 + dim.offset_y * 0.75,
             BAR_FONT_SIZE.1,
-            BAR_TEXT_COLOR,
+            theme.background,
         );
     }
 
-    fn draw_dock(&mut self) {
+    /// Icon, window, visibility and space in the dock of every window with an icon, left to
+    /// right
+    fn dock_icons(&self) -> Vec<(Texture2D, WindowId, bool, Rect)> {
         let icons = self
             .windows
             .iter()
+            .filter_map(|(id, win)| Some((win.icon(&self.context)?, id, win.is_visible())))
+            .collect::<Vec<_>>();
+
+        let cell = (DOCK_ICON_SIZE + DOCK_SPACING) as f32;
+        let left = self.context.renderer.screen_size().x * 0.5 - icons.len() as f32 * cell * 0.5;
+        let top = self.context.renderer.screen_size().y - (cell + DOCK_SPACING as f32);
+        icons
+            .into_iter()
             .enumerate()
-            .map(|(index, win)| (win.icon(), index))
-            .filter_map(|(opt, index)| match opt {
-                Some(texture) => Some((texture, index)),
-                None => None,
+            .map(|(index, (icon, id, is_visible))| {
+                let rect = Rect::new(left + index as f32 * cell, top, cell, cell);
+                (icon, id, is_visible, rect)
             })
-            .collect::<LinkedList<_>>();
+            .collect()
+    }
 
-        if icons.is_empty() {
+    fn draw_dock(&self) {
+        let icons = self.dock_icons();
+        let (Some((.., first)), Some((.., last))) = (icons.first(), icons.last()) else {
             return;
-        }
+        };
+        let theme = self.context.theme;
+        self.context.renderer.draw_outlined_box(
+            Rect::new(first.x, first.y, last.right() - first.x, first.h),
+            5.0,
+            theme.background,
+            theme.foreground,
+        );
 
-        let width = (icons.len() as u32 * (DOCK_ICON_SIZE + DOCK_SPACING)) as f32;
-        let height = (DOCK_ICON_SIZE + DOCK_SPACING) as f32;
-        let left = screen_width() * 0.5 - width * 0.5;
-        let top = screen_height() - (height + DOCK_SPACING as f32);
-        draw_outlined_box(left, top, width, height, 5.0, BG_COLOR, FG_COLOR);
-
-        // Transparent button style
-        let darker = Color::from_rgba(0, 0, 0, 50);
-        let button_style = root_ui()
-            .style_builder()
-            .color(BLANK)
-            .color_hovered(darker)
-            .color_clicked(darker)
-            .text_color(BLANK)
-            .text_color_hovered(BLANK)
-            .build();
-        let mut skin = root_ui().default_skin();
-        skin.button_style = button_style;
-        root_ui().push_skin(&skin);
-        let mut x = left;
-        for (icon, win_index) in icons {
-            draw_texture(
-                &icon,
-                x + DOCK_SPACING as f32 * 0.5,
-                top + DOCK_SPACING as f32 * 0.5,
-                BG_COLOR,
+        for (icon, _, is_visible, rect) in &icons {
+            self.context.renderer.draw_texture(
+                icon,
+                rect.x + DOCK_SPACING as f32 * 0.5,
+                rect.y + DOCK_SPACING as f32 * 0.5,
+                theme.background,
             );
-
-            if self.windows[win_index].is_visible() {
-                draw_circle(
-                    x + (DOCK_ICON_SIZE + DOCK_SPACING) as f32 * 0.5,
-                    screen_height() - 8.0,
+            if rect.contains(self.context.input.mouse_pos) {
+                self.context.renderer.draw_rectangle(
+                    rect.x,
+                    rect.y,
+                    rect.w,
+                    rect.h,
+                    DOCK_HOVER_COLOR,
+                );
+            }
+            if *is_visible {
+                self.context.renderer.draw_circle(
+                    rect.center().x,
+                    self.context.renderer.screen_size().y - 8.0,
                     4.0,
-                    FG_COLOR,
+                    theme.foreground,
                 );
             }
+        }
+    }
+
+    /// Returns false if the position is not on an icon of the dock
+    fn press_dock(&mut self, pos: Vec2) -> bool {
+        let Some((_, win_id, ..)) = self
+            .dock_icons()
+            .into_iter()
+            .find(|(.., rect)| rect.contains(pos))
+        else {
+            return false;
+        };
 
-            // Add a transparent button
-            if Button::new("")
-                .size(Vec2::new(
-                    (DOCK_ICON_SIZE + DOCK_SPACING) as f32,
-                    (DOCK_ICON_SIZE + DOCK_SPACING) as f32,
-                ))
-                .position(Vec2::new(x, top))
-                .ui(&mut root_ui())
-            {
-                let is_visible = self.windows[win_index].is_visible();
-                self.windows[win_index].set_visibility(!is_visible);
+        // Only the window in front is minimized, any other one is brought to the front
+        if self.windows.focused() == Some(win_id) {
+            self.windows.hide(win_id);
+        } else {
+            self.windows.raise(win_id);
+        }
+        true
+    }
+
+    fn on_volume_mounted(&mut self, volume: PathBuf) {
+        self.log_event(format!("Volume mounted: {}", volume.display()));
+
+        let mut rejected = false;
+        for config in self.context.config.payloads.clone() {
+            let Ok(content) = fs::read_to_string(volume.join(&config.file)) else {
+                continue;
+            };
+            match payload::verify(&content, &self.hack_key) {
+                Payload::Valid(body)
+                    if payload::declared_id(&body).is_none_or(|id| id == config.id) =>
+                {
+                    self.payload_volumes.insert(volume.clone());
+                    let is_active_hack = self
+                        .active_hack
+                        .as_ref()
+                        .is_some_and(|(_, id)| *id == config.id);
+                    if is_active_hack && self.hack_paused {
+                        self.resume_hack(volume.clone());
+                    } else if is_active_hack {
+                        // E.g. still plugged in after resuming a session, but mounted at
+                        // a different path
+                        self.active_hack = Some((volume.clone(), config.id.clone()));
+                    } else {
+                        self.fire_payload(&config, &volume, &body);
+                    }
+                }
+                _ => {
+                    self.log_event(format!("Payload `{}` rejected", config.id));
+                    rejected = true;
+                }
             }
+        }
 
-            x += (DOCK_ICON_SIZE + DOCK_SPACING) as f32;
+        if rejected {
+            self.windows.push(Box::new(PopUp::new_with_text(
+                &self.context,
+                self.context.config.hack.invalid_payload_text.clone(),
+            )));
+        }
+    }
+
+    fn fire_payload(&mut self, config: &PayloadConfig, volume: &Path, body: &str) {
+        if !self.usb_armed {
+            self.log_event(format!(
+                "Payload `{}` ignored until the USB drive is removed",
+                config.id
+            ));
+            return;
+        }
+        if self.fired_payloads.contains(&config.id) {
+            self.log_event(format!("Payload `{}` was already used", config.id));
+            return;
         }
 
-        root_ui().pop_skin();
+        match &config.action {
+            PayloadAction::Hack => {
+                if self.hack_status != HackStatus::NoUSB {
+                    return;
+                }
+                let script = match hack_script::parse(body) {
+                    Ok(script) => script,
+                    Err(err) => {
+                        self.log_event(format!(
+                            "Payload `{}` has an invalid script: {err}",
+                            config.id
+                        ));
+                        self.windows.push(Box::new(PopUp::new_with_text(
+                            &self.context,
+                            self.context.config.hack.invalid_payload_text.clone(),
+                        )));
+                        return;
+                    }
+                };
+                self.hack_status = HackStatus::USBOpened;
+                self.active_hack = Some((volume.to_path_buf(), config.id.clone()));
+                self.windows
+                    .push(Box::new(HackConsole::new(&self.context, script)));
+            }
+            PayloadAction::Unlock => self.force_unlock(),
+            PayloadAction::Message { text } => {
+                self.windows
+                    .push(Box::new(PopUp::new_with_text(&self.context, text.clone())));
+            }
+            PayloadAction::Documents { documents } => {
+                for name in documents {
+                    self.open_document(name.clone());
+                }
+            }
+            PayloadAction::Puzzle { minigame, .. } => {
+                // Not used up, the drive can be plugged in again after the hack
+                if matches!(
+                    self.hack_status,
+                    HackStatus::USBOpened | HackStatus::Minigame
+                ) {
+                    self.log_event(format!(
+                        "Payload `{}` ignored while the hack is running",
+                        config.id
+                    ));
+                    return;
+                }
+                self.windows.push(Box::new(MiniGame::for_puzzle(
+                    minigame.clone(),
+                    config.id.clone(),
+                )));
+            }
+        }
+
+        self.log_event(format!("Payload `{}` fired", config.id));
+        self.fired_payloads.insert(config.id.clone());
+        // The hack and puzzles are completed once their minigame is solved
+        if !matches!(
+            config.action,
+            PayloadAction::Hack | PayloadAction::Puzzle { .. }
+        ) {
+            self.filesystem.complete_payload(&config.id);
+        }
     }
 
-    fn check_hack_file(&mut self) -> bool {
-        let diff = Instant::now().duration_since(self.last_usb_check);
-        if diff.as_secs_f32() < 1.0 {
-            return false;
+    fn on_volume_removed(&mut self, volume: PathBuf) {
+        self.log_event(format!("Volume removed: {}", volume.display()));
+
+        self.payload_volumes.remove(&volume);
+        if self
+            .active_hack
+            .as_ref()
+            .is_some_and(|(hack_volume, _)| *hack_volume == volume)
+            && !self.hack_paused
+        {
+            self.on_hack_usb_removed();
+        }
+        if !self.usb_armed && self.payload_volumes.is_empty() {
+            self.log_event("USB detection re-armed".to_string());
+            self.usb_armed = true;
+        }
+    }
+
+    /// The drive that started the hack was pulled out before the hack was completed
+    fn on_hack_usb_removed(&mut self) {
+        if !matches!(
+            self.hack_status,
+            HackStatus::USBOpened | HackStatus::Minigame
+        ) {
+            return;
         }
 
-        self.last_usb_check = Instant::now();
-        let maybe_content = fs::read_to_string(self.usb_path.join(HACK_FILE_NAME)).ok();
-        match maybe_content {
-            Some(content) => content == self.hack_file_content,
-            None => false,
+        match self.context.config.hack.on_usb_removed {
+            UsbRemovedBehavior::Abort => {
+                let Some((_, id)) = self.active_hack.take() else {
+                    return;
+                };
+                self.windows.retain(|win| {
+                    !matches!(win.kind(), WindowKind::MiniGame | WindowKind::HackConsole)
+                });
+                self.windows.push(Box::new(PopUp::new_with_text(
+                    &self.context,
+                    self.context.config.hack.connection_lost_text.clone(),
+                )));
+                self.hack_status = HackStatus::NoUSB;
+                // The same drive may be used again to start over
+                self.fired_payloads.remove(&id);
+                self.log_event("Hack aborted, USB drive removed".to_string());
+            }
+            UsbRemovedBehavior::Pause => {
+                self.hack_paused = true;
+                for win in self.windows.iter_mut() {
+                    win.set_paused(true);
+                }
+                self.log_event("Hack paused, USB drive removed".to_string());
+            }
+            UsbRemovedBehavior::Ignore => {
+                self.log_event("USB drive removed during the hack, ignoring".to_string());
+            }
         }
     }
-}
 
-impl Drop for EscOS {
-    fn drop(&mut self) {
-        self.udiskie.kill().expect("Failed to kill udiskie!");
+    /// The drive of a paused hack is back, possibly mounted at a different path
+    fn resume_hack(&mut self, volume: PathBuf) {
+        self.hack_paused = false;
+        if let Some((hack_volume, _)) = &mut self.active_hack {
+            *hack_volume = volume;
+        }
+        for win in self.windows.iter_mut() {
+            win.set_paused(false);
+        }
+        self.log_event("Hack resumed, USB drive reinserted".to_string());
     }
 }
@@ -0,0 +1,546 @@
+use std::{mem, rc::Rc, time::Duration};
+
+use macroquad::prelude::*;
+
+use crate::{
+    context::AppContext,
+    input::InputEvent,
+    render::Renderer,
+    scenario::{FileKind, FileLock, TerminalConfig},
+    session::{WindowSnapshot, WindowState},
+    vfs::{self, Locked, VirtualFile, VirtualFs},
+    window_chrome::{ChromeAction, WindowChrome},
+    windows::{Window, WindowKind, WindowReturnAction, HEADER_HEIGHT},
+};
+
+const WIDTH: f32 = 1000.0;
+const HEIGHT: f32 = 600.0;
+const MIN_SIZE: Vec2 = vec2(400.0, 250.0);
+const FONT_SIZE: f32 = 28.0;
+const LINE_HEIGHT: f32 = 32.0;
+const PADDING: f32 = 20.0;
+const MAX_SCROLLBACK: usize = 500;
+
+const HELP: &str = "Available commands:
+  help                       show this help
+  ls [-a] [-l] [folder]      list a folder, -a shows hidden files
+  cd [folder]                change the current folder
+  cat <file>                 print a file or open a document
+  decrypt <file> <password>  unlock an encrypted file or folder
+  whoami                     print the current user
+  clear                      clear the screen";
+
+/// Fake shell for exploring the virtual filesystem of the scenario
+pub struct TerminalWindow {
+    chrome: WindowChrome,
+    config: TerminalConfig,
+    filesystem: Rc<VirtualFs>,
+    cwd: String,
+    scrollback: Vec<String>,
+    /// `scrollback` wrapped to the text width it was last drawn with, `None` after it changed
+    wrapped_scrollback: Option<(f32, Vec<String>)>,
+    input: String,
+    history: Vec<String>,
+    /// Index into `history` while browsing it with the arrow keys
+    history_index: Option<usize>,
+    /// Lines scrolled up from the bottom
+    scroll_offset: usize,
+    /// Returned from the next `handle_input`, e.g. to open a document
+    pending_action: Option<WindowReturnAction>,
+
+    is_visible: bool,
+    is_focused: bool,
+}
+
+impl TerminalWindow {
+    pub fn new(context: &AppContext, config: TerminalConfig, filesystem: Rc<VirtualFs>) -> Self {
+        TerminalWindow {
+            chrome: WindowChrome::new(
+                context.renderer.screen_size() * 0.5,
+                vec2(WIDTH, HEIGHT),
+                MIN_SIZE,
+            )
+            // Opened once when the system is unlocked
+            .without_close_button(),
+            cwd: config.home.clone(),
+            config,
+            filesystem,
+            scrollback: vec!["Type `help` to see the available commands.".to_string()],
+            wrapped_scrollback: None,
+            input: String::new(),
+            history: vec![],
+            history_index: None,
+            scroll_offset: 0,
+            pending_action: None,
+
+            is_visible: true,
+            is_focused: false,
+        }
+    }
+
+    pub fn restore_boxed(
+        context: &AppContext,
+        filesystem: Rc<VirtualFs>,
+        position: Vec2,
+        size: Option<Vec2>,
+        cwd: String,
+        scrollback: Vec<String>,
+    ) -> Box<dyn Window> {
+        let mut terminal = Self::new(context, context.config.terminal.clone(), filesystem);
+        if let Some(size) = size {
+            terminal.chrome.set_size(size);
+        }
+        terminal.chrome.set_position(position);
+        // The scenario may have changed since the snapshot was taken
+        if terminal.filesystem.is_folder(&cwd) {
+            terminal.cwd = cwd;
+        }
+        terminal.scrollback = scrollback;
+
+        Box::new(terminal)
+    }
+
+    fn prompt(&self) -> String {
+        let cwd = match self.cwd.strip_prefix(&self.config.home) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{rest}"),
+            _ => self.cwd.clone(),
+        };
+
+        format!("{}@{}:{cwd}$ ", self.config.user, self.config.hostname)
+    }
+
+    fn print(&mut self, text: &str) {
+        self.scrollback.extend(text.lines().map(str::to_string));
+        let overflow = self.scrollback.len().saturating_sub(MAX_SCROLLBACK);
+        self.scrollback.drain(..overflow);
+        self.wrapped_scrollback = None;
+    }
+
+    fn resolve(&self, path: &str) -> String {
+        vfs::resolve(&self.cwd, &self.config.home, path)
+    }
+
+    /// `now` is the clock time, for time locks
+    fn run(&mut self, line: &str, now: Duration) {
+        self.print(&format!("{}{line}", self.prompt()));
+        if !line.trim().is_empty() {
+            self.history.push(line.to_string());
+        }
+        self.history_index = None;
+        self.scroll_offset = 0;
+
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        let Some((&command, args)) = args.split_first() else {
+            return;
+        };
+        let output = match (command, args) {
+            ("help", _) => HELP.to_string(),
+            ("whoami", _) => self.config.user.clone(),
+            ("clear", _) => {
+                self.scrollback.clear();
+                self.wrapped_scrollback = None;
+                return;
+            }
+            ("ls", args) => self.ls(args, now),
+            ("cd", []) => {
+                self.cwd = self.config.home.clone();
+                return;
+            }
+            ("cd", [folder]) => match self.cd(folder, now) {
+                Ok(()) => return,
+                Err(err) => err,
+            },
+            ("cat", [file]) => self.cat(file, now),
+            ("decrypt", [file, password]) => self.decrypt(file, password, now),
+            ("cd" | "cat" | "decrypt", _) => {
+                format!("{command}: wrong number of arguments, see `help`")
+            }
+            _ => format!("{command}: command not found"),
+        };
+
+        self.print(&output);
+    }
+
+    /// Supports `-a` for hidden files and `-l` for details
+    fn ls(&self, args: &[&str], now: Duration) -> String {
+        let (flags, paths) = args
+            .iter()
+            .partition::<Vec<&str>, _>(|arg| arg.starts_with('-'));
+        let show_hidden = flags.iter().any(|flag| flag.contains('a'));
+        let long = flags.iter().any(|flag| flag.contains('l'));
+        let name = match paths.as_slice() {
+            [] => ".",
+            [path] => path,
+            _ => return "ls: wrong number of arguments, see `help`".to_string(),
+        };
+
+        let path = self.resolve(name);
+        if let Some((node, locked)) = self.filesystem.locked(&path, now) {
+            return locked_message("ls", node, locked);
+        }
+        let Some(entries) = self.filesystem.list(&path, show_hidden) else {
+            return format!("ls: {name}: No such folder");
+        };
+
+        if !long {
+            return entries
+                .iter()
+                .map(|entry| match entry.is_folder() {
+                    true => format!("{}/", entry.name()),
+                    false => entry.name().to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+        }
+        entries
+            .iter()
+            .map(|entry| {
+                let kind = match entry.kind {
+                    FileKind::Folder => 'd',
+                    FileKind::Document => 'i',
+                    FileKind::Text => '-',
+                };
+                let lock = if self.filesystem.locked(&entry.path, now).is_some() {
+                    "locked"
+                } else {
+                    ""
+                };
+                format!("{kind} {:<10} {lock:<6} {}", entry.owner, entry.name())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn cd(&mut self, name: &str, now: Duration) -> Result<(), String> {
+        let path = self.resolve(name);
+        if !self.filesystem.is_folder(&path) {
+            return Err(format!("cd: {name}: No such folder"));
+        }
+        if let Some((node, locked)) = self.filesystem.locked(&path, now) {
+            return Err(locked_message("cd", node, locked));
+        }
+
+        self.cwd = path;
+        Ok(())
+    }
+
+    fn cat(&mut self, name: &str, now: Duration) -> String {
+        let filesystem = self.filesystem.clone();
+        let path = self.resolve(name);
+        let Some(file) = filesystem.get(&path) else {
+            return format!("cat: {name}: No such file");
+        };
+        if let Some((node, locked)) = filesystem.locked(&path, now) {
+            return locked_message("cat", node, locked);
+        }
+
+        match file.kind {
+            FileKind::Folder => format!("cat: {name}: Is a folder"),
+            FileKind::Text => file.content.clone(),
+            FileKind::Document => self.open_document(file),
+        }
+    }
+
+    fn open_document(&mut self, file: &VirtualFile) -> String {
+        if let Some(document) = &file.document {
+            self.pending_action = Some(WindowReturnAction::OpenDocument(document.clone()));
+        }
+
+        format!("Opening {}...", file.name())
+    }
+
+    fn decrypt(&mut self, name: &str, password: &str, now: Duration) -> String {
+        let filesystem = self.filesystem.clone();
+        let path = self.resolve(name);
+        let Some(file) = filesystem.get(&path) else {
+            return format!("decrypt: {name}: No such file");
+        };
+        if !matches!(file.lock, Some(FileLock::Password { .. })) {
+            return format!("decrypt: {name}: Not encrypted");
+        }
+        if !filesystem.unlock_with_password(&path, password) {
+            return "decrypt: Wrong password".to_string();
+        }
+
+        // A folder above may still be locked
+        if let Some((node, locked)) = filesystem.locked(&path, now) {
+            return locked_message("decrypt", node, locked);
+        }
+        match file.kind {
+            FileKind::Folder => format!("{name}: Access granted"),
+            FileKind::Text => file.content.clone(),
+            FileKind::Document => self.open_document(file),
+        }
+    }
+
+    fn handle_keyboard(&mut self, event: InputEvent, now: Duration) {
+        let key = match event {
+            InputEvent::Text(char) => {
+                self.input.push(char);
+                return;
+            }
+            InputEvent::KeyDown { key, .. } => key,
+            _ => return,
+        };
+
+        match key {
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter | KeyCode::KpEnter => {
+                let line = mem::take(&mut self.input);
+                self.run(&line, now);
+            }
+            KeyCode::Up if !self.history.is_empty() => {
+                let index = match self.history_index {
+                    Some(index) => index.saturating_sub(1),
+                    None => self.history.len() - 1,
+                };
+                self.history_index = Some(index);
+                self.input = self.history[index].clone();
+            }
+            KeyCode::Down => {
+                if let Some(index) = self.history_index {
+                    if index + 1 < self.history.len() {
+                        self.history_index = Some(index + 1);
+                        self.input = self.history[index + 1].clone();
+                    } else {
+                        self.history_index = None;
+                        self.input.clear();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn locked_message(command: &str, node: &VirtualFile, locked: Locked) -> String {
+    let name = node.name();
+    match locked {
+        Locked::Password => format!("{command}: {name}: Encrypted, use `decrypt`"),
+        Locked::Payload => format!("{command}: {name}: Access denied, authorization key required"),
+        Locked::Time(remaining) => {
+            let secs = remaining.as_secs() + 1;
+            format!(
+                "{command}: {name}: Time locked, opens in {}:{:02}",
+                secs / 60,
+                secs % 60
+            )
+        }
+    }
+}
+
+/// Splits the line at spaces so that every part fits into `max_width`, words that are too
+/// long for a line of their own are split anywhere. The widths of the words are added up
+/// instead of measuring the whole part again after every word.
+fn wrap_line(renderer: &dyn Renderer, line: &str, max_width: f32) -> Vec<String> {
+    let width = |text: &str| renderer.measure_text(text, FONT_SIZE as u16, 1.0).width;
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut current_width = 0.0;
+    let mut break_line = |current: &mut String, current_width: &mut f32| {
+        parts.push(current.trim_end().to_string());
+        current.clear();
+        *current_width = 0.0;
+    };
+
+    // Each word keeps the space after it
+    for word in line.split_inclusive(' ') {
+        // A trailing space may hang over the edge
+        let word_width = width(word.trim_end());
+        if current_width + word_width > max_width && !current.is_empty() {
+            break_line(&mut current, &mut current_width);
+        }
+        if word_width <= max_width {
+            current.push_str(word);
+            current_width += width(word);
+            continue;
+        }
+
+        for char in word.chars() {
+            let char_width = width(char.encode_utf8(&mut [0; 4]));
+            if current_width + char_width > max_width && !current.is_empty() {
+                break_line(&mut current, &mut current_width);
+            }
+            current.push(char);
+            current_width += char_width;
+        }
+    }
+    parts.push(current);
+
+    parts
+}
+
+impl Window for TerminalWindow {
+    fn position(&self) -> Vec2 {
+        self.chrome.position()
+    }
+
+    fn top_left(&self) -> Vec2 {
+        self.chrome.top_left()
+    }
+
+    fn size(&self) -> Vec2 {
+        self.chrome.size()
+    }
+
+    fn draw(&mut self, context: &AppContext) {
+        let theme = &context.theme;
+        let tl = self.top_left();
+        let size = self.size();
+        context.renderer.draw_outlined_box(
+            Rect::new(tl.x, tl.y, size.x, size.y),
+            5.0,
+            theme.foreground,
+            theme.foreground,
+        );
+        self.chrome.draw(context, &self.title(), self.is_focused);
+
+        let cursor = if self.is_focused && context.clock.cursor_visible() {
+            "_"
+        } else {
+            ""
+        };
+        let renderer = context.renderer.as_ref();
+        let max_width = size.x - 2.0 * PADDING;
+        let input_line = format!("{}{}{cursor}", self.prompt(), self.input);
+        let input_lines = wrap_line(renderer, &input_line, max_width);
+        // Only the input line changes from frame to frame
+        if self
+            .wrapped_scrollback
+            .as_ref()
+            .is_some_and(|(width, _)| *width != max_width)
+        {
+            self.wrapped_scrollback = None;
+        }
+        let scrollback = &self.scrollback;
+        let (_, wrapped) = self.wrapped_scrollback.get_or_insert_with(|| {
+            let lines = scrollback
+                .iter()
+                .flat_map(|line| wrap_line(renderer, line, max_width))
+                .collect();
+            (max_width, lines)
+        });
+        let line_count = wrapped.len() + input_lines.len();
+
+        let max_lines = ((size.y - HEADER_HEIGHT - 2.0 * PADDING) / LINE_HEIGHT) as usize;
+        self.scroll_offset = self.scroll_offset.min(line_count.saturating_sub(max_lines));
+        let last = line_count - self.scroll_offset;
+        let first = last.saturating_sub(max_lines);
+
+        let mut y = tl.y + HEADER_HEIGHT + PADDING + FONT_SIZE * 0.75;
+        for line in wrapped.iter().chain(&input_lines).take(last).skip(first) {
+            context
+                .renderer
+                .draw_text(line, tl.x + PADDING, y, FONT_SIZE, theme.background);
+            y += LINE_HEIGHT;
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    fn set_visibility(&mut self, value: bool) {
+        self.is_visible = value;
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.is_focused = focused;
+    }
+
+    fn update(&mut self, context: &AppContext) -> WindowReturnAction {
+        self.chrome.update(context);
+        WindowReturnAction::None
+    }
+
+    fn handle_input(&mut self, context: &AppContext, event: InputEvent) -> WindowReturnAction {
+        match self.chrome.handle_input(context, &event) {
+            ChromeAction::None => {}
+            ChromeAction::Handled => return WindowReturnAction::None,
+            ChromeAction::Minimize => return WindowReturnAction::Minimize,
+            ChromeAction::Close => return WindowReturnAction::Close,
+        }
+
+        match event {
+            InputEvent::Wheel { delta, .. } if delta.y != 0.0 => {
+                self.scroll_offset = if delta.y > 0.0 {
+                    self.scroll_offset + 3
+                } else {
+                    self.scroll_offset.saturating_sub(3)
+                };
+            }
+            _ => self.handle_keyboard(event, context.clock.now()),
+        }
+
+        self.pending_action
+            .take()
+            .unwrap_or(WindowReturnAction::None)
+    }
+
+    fn icon(&self, context: &AppContext) -> Option<Texture2D> {
+        context.textures.document()
+    }
+
+    fn kind(&self) -> WindowKind {
+        WindowKind::Terminal
+    }
+
+    fn title(&self) -> String {
+        "Terminal".to_string()
+    }
+
+    fn snapshot(&self) -> Option<WindowSnapshot> {
+        Some(WindowSnapshot {
+            position: self.position().into(),
+            size: Some(self.size().into()),
+            is_visible: self.is_visible,
+            state: WindowState::Terminal {
+                cwd: self.cwd.clone(),
+                scrollback: self.scrollback.clone(),
+            },
+        })
+    }
+
+    fn contains_pos(&self, pos: Vec2) -> bool {
+        self.chrome.contains_pos(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::HeadlessRenderer;
+
+    /// Ten characters of the headless renderer
+    const TEN_CHARS: f32 = FONT_SIZE * 0.5 * 10.0;
+
+    fn wrap(line: &str) -> Vec<String> {
+        let renderer = HeadlessRenderer {
+            screen_size: vec2(1920.0, 1080.0),
+        };
+        wrap_line(&renderer, line, TEN_CHARS)
+    }
+
+    #[test]
+    fn breaks_between_words() {
+        assert_eq!(wrap("cat secret notes"), ["cat secret", "notes"]);
+        assert_eq!(wrap("  ls -a -l folder"), ["  ls -a -l", "folder"]);
+        assert_eq!(wrap(""), [""]);
+    }
+
+    #[test]
+    fn splits_words_longer_than_a_line() {
+        assert_eq!(
+            wrap("ls /home/agent/documents"),
+            ["ls", "/home/agen", "t/document", "s"]
+        );
+    }
+
+    #[test]
+    fn keeps_spaces_at_the_end() {
+        // The cursor of the input line goes after them
+        assert_eq!(wrap("agent$ "), ["agent$ "]);
+    }
+}
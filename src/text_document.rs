@@ -0,0 +1,545 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use macroquad::prelude::*;
+
+use crate::document_meta::DocumentLink;
+
+/// Same width as the scanned documents
+pub const PAGE_WIDTH: f32 = 850.0;
+/// Short documents are padded to a sheet of paper
+pub const MIN_PAGE_HEIGHT: f32 = 1100.0;
+/// Larger textures are not supported by every GPU, the rest is cut off
+const MAX_PAGE_HEIGHT: f32 = 8192.0;
+const MARGIN: f32 = 60.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+
+const BODY_SIZE: u16 = 26;
+const CODE_SIZE: u16 = 24;
+const HEADING_SIZES: [u16; 3] = [48, 38, 32];
+const LINE_SPACING: f32 = 1.3;
+const BLOCK_GAP: f32 = 14.0;
+const CODE_PADDING: f32 = 12.0;
+const LIST_INDENT: f32 = 30.0;
+
+const TEXT_COLOR: Color = BLACK;
+const LINK_COLOR: Color = Color::from_hex(0x1A4FB0);
+const CODE_BG_COLOR: Color = Color::from_hex(0xE8E8E8);
+const RULE_COLOR: Color = Color::from_hex(0xA0A0A0);
+
+/// Part of a line with the same style
+#[derive(PartialEq, Debug)]
+struct Span {
+    text: String,
+    bold: bool,
+    /// Name of the linked document
+    link: Option<String>,
+}
+
+#[derive(PartialEq, Debug)]
+enum Block {
+    /// Level 1 to 3
+    Heading(usize, String),
+    Paragraph(Vec<Span>),
+    ListItem(Vec<Span>),
+    /// Monospace lines, not wrapped
+    Code(Vec<String>),
+    /// Path relative to the document
+    Image(String),
+    Rule,
+    Blank,
+}
+
+enum DrawOp {
+    Text {
+        text: String,
+        x: f32,
+        y: f32,
+        size: u16,
+        bold: bool,
+        link: Option<String>,
+    },
+    Rect(Rect, Color),
+    Image(Texture2D, Rect),
+}
+
+/// Renders a `.txt` or `.md` file into one texture per page that the document window can
+/// scroll like a scanned document. Also returns the links and the source for searching.
+pub async fn load(path: &Path) -> Result<(Vec<Texture2D>, Vec<DocumentLink>, String), String> {
+    let source = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read `{}`: {err}", path.display()))?;
+
+    let is_markdown = path.extension().is_some_and(|ext| ext == "md");
+    let folder = path.parent().unwrap_or(Path::new("."));
+    let mut pages = vec![];
+    let mut links = vec![];
+    for (index, page) in split_pages(&source).iter().enumerate() {
+        let (texture, page_links) = render_page(page, is_markdown, folder).await;
+        pages.push(texture);
+        links.extend(page_links.into_iter().map(|(target, rect)| DocumentLink {
+            target,
+            page: index + 1,
+            rect: [rect.x, rect.y, rect.w, rect.h],
+        }));
+    }
+
+    Ok((pages, links, source))
+}
+
+/// A line with only `\pagebreak` or a form feed starts a new page
+fn split_pages(source: &str) -> Vec<String> {
+    let mut pages = vec![String::new()];
+    for line in source.lines() {
+        if matches!(line.trim_matches([' ', '\t']), "\\pagebreak" | "\u{c}") {
+            pages.push(String::new());
+        } else if let Some(page) = pages.last_mut() {
+            page.push_str(line);
+            page.push('\n');
+        }
+    }
+
+    pages
+}
+
+async fn render_page(
+    source: &str,
+    is_markdown: bool,
+    folder: &Path,
+) -> (Texture2D, Vec<(String, Rect)>) {
+    let blocks = if is_markdown {
+        parse_markdown(source)
+    } else {
+        parse_text(source)
+    };
+
+    let mut images = HashMap::new();
+    for block in &blocks {
+        if let Block::Image(image) = block {
+            let image_path = folder.join(image);
+            match load_texture(&image_path.to_string_lossy()).await {
+                Ok(texture) => {
+                    images.insert(image.clone(), texture);
+                }
+                Err(err) => eprintln!("Failed to load `{}`: {err}", image_path.display()),
+            }
+        }
+    }
+
+    let (ops, height) = layout(&blocks, &images);
+    (render(&ops, height), links(&ops))
+}
+
+/// Renders Markdown without images, for documents made up while the game runs
+pub fn render_markdown(source: &str) -> Texture2D {
+    let (ops, height) = layout(&parse_markdown(source), &HashMap::new());
+    render(&ops, height)
+}
+
+/// First heading of a Markdown document
+pub fn title(source: &str) -> Option<String> {
+    source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+}
+
+/// Every line is kept as it is, long lines are wrapped
+fn parse_text(source: &str) -> Vec<Block> {
+    source
+        .lines()
+        .map(|line| match line.trim_end() {
+            "" => Block::Blank,
+            line => Block::Paragraph(vec![Span {
+                text: line.to_string(),
+                bold: false,
+                link: None,
+            }]),
+        })
+        .collect()
+}
+
+/// The subset writers need: `#` headings, `**bold**`, `[links](document)`, `-` lists,
+/// fenced code blocks, `![alt](path)` images on their own line and `---` rules. Consecutive
+/// lines form one paragraph.
+fn parse_markdown(source: &str) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut paragraph: Vec<&str> = vec![];
+    let mut code: Option<Vec<String>> = None;
+
+    for line in source.lines() {
+        if let Some(lines) = code.as_mut() {
+            if line.trim_start().starts_with("```") {
+                blocks.push(Block::Code(code.take().unwrap_or_default()));
+            } else {
+                lines.push(line.to_string());
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        let is_paragraph_line = !trimmed.is_empty()
+            && !trimmed.starts_with('#')
+            && !trimmed.starts_with("```")
+            && !trimmed.starts_with("- ")
+            && !trimmed.starts_with("* ")
+            && trimmed != "---"
+            && image_path(trimmed).is_none();
+        if is_paragraph_line {
+            paragraph.push(trimmed);
+            continue;
+        }
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(parse_spans(&paragraph.join(" "))));
+            paragraph.clear();
+        }
+
+        if trimmed.is_empty() {
+            blocks.push(Block::Blank);
+        } else if trimmed.starts_with("```") {
+            code = Some(vec![]);
+        } else if trimmed == "---" {
+            blocks.push(Block::Rule);
+        } else if let Some(image) = image_path(trimmed) {
+            blocks.push(Block::Image(image.to_string()));
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            blocks.push(Block::ListItem(parse_spans(item)));
+        } else {
+            let level = trimmed.chars().take_while(|c| *c == '#').count();
+            let text = trimmed.trim_start_matches('#').trim();
+            blocks.push(Block::Heading(
+                level.min(HEADING_SIZES.len()),
+                text.to_string(),
+            ));
+        }
+    }
+
+    if !paragraph.is_empty() {
+        blocks.push(Block::Paragraph(parse_spans(&paragraph.join(" "))));
+    }
+    // An unclosed block runs to the end of the file
+    if let Some(lines) = code {
+        blocks.push(Block::Code(lines));
+    }
+
+    blocks
+}
+
+/// `![alt](path)`
+fn image_path(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("![")?;
+    let (_alt, rest) = rest.split_once("](")?;
+    rest.strip_suffix(')')
+}
+
+/// Splits at `**`, every other span is bold. `[label](document)` links to a document.
+fn parse_spans(text: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut push = |text: &str, bold: bool, link: Option<&str>| {
+        if !text.is_empty() {
+            spans.push(Span {
+                text: text.to_string(),
+                bold,
+                link: link.map(str::to_string),
+            });
+        }
+    };
+
+    for (index, part) in text.split("**").enumerate() {
+        let bold = index % 2 == 1;
+        let mut rest = part;
+        while let Some((before, label, target, after)) = next_link(rest) {
+            push(before, bold, None);
+            push(label, bold, Some(target));
+            rest = after;
+        }
+        push(rest, bold, None);
+    }
+
+    spans
+}
+
+/// Text before the link, label, target and text after the link
+fn next_link(text: &str) -> Option<(&str, &str, &str, &str)> {
+    let mut search_from = 0;
+    while let Some(start) = text[search_from..]
+        .find('[')
+        .map(|index| index + search_from)
+    {
+        let link = text[start + 1..].split_once(']').and_then(|(label, rest)| {
+            let (target, after) = rest.strip_prefix('(')?.split_once(')')?;
+            (!label.contains('[')).then_some((label, target, after))
+        });
+        if let Some((label, target, after)) = link {
+            return Some((&text[..start], label, target.trim(), after));
+        }
+        search_from = start + 1;
+    }
+
+    None
+}
+
+/// Positions everything on the page, returns the draw operations and the page height
+fn layout(blocks: &[Block], images: &HashMap<String, Texture2D>) -> (Vec<DrawOp>, f32) {
+    let mut ops = vec![];
+    let mut y = MARGIN;
+
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                let size = HEADING_SIZES[level.saturating_sub(1)];
+                let span = Span {
+                    text: text.clone(),
+                    bold: true,
+                    link: None,
+                };
+                y = layout_spans(&mut ops, &[span], MARGIN, y, size);
+                y += BLOCK_GAP;
+            }
+            Block::Paragraph(spans) => {
+                y = layout_spans(&mut ops, spans, MARGIN, y, BODY_SIZE);
+            }
+            Block::ListItem(spans) => {
+                ops.push(DrawOp::Text {
+                    text: "-".to_string(),
+                    x: MARGIN + LIST_INDENT * 0.3,
+                    y: y + BODY_SIZE as f32,
+                    size: BODY_SIZE,
+                    bold: false,
+                    link: None,
+                });
+                y = layout_spans(&mut ops, spans, MARGIN + LIST_INDENT, y, BODY_SIZE);
+            }
+            Block::Code(lines) => {
+                let line_height = CODE_SIZE as f32 * LINE_SPACING;
+                let height = lines.len() as f32 * line_height + 2.0 * CODE_PADDING;
+                ops.push(DrawOp::Rect(
+                    Rect::new(MARGIN, y, CONTENT_WIDTH, height),
+                    CODE_BG_COLOR,
+                ));
+                let mut line_y = y + CODE_PADDING;
+                for line in lines {
+                    ops.push(DrawOp::Text {
+                        text: line.clone(),
+                        x: MARGIN + CODE_PADDING,
+                        y: line_y + CODE_SIZE as f32,
+                        size: CODE_SIZE,
+                        bold: false,
+                        link: None,
+                    });
+                    line_y += line_height;
+                }
+                y += height + BLOCK_GAP;
+            }
+            Block::Image(path) => match images.get(path) {
+                Some(texture) => {
+                    // Only scaled down, never up
+                    let scale = (CONTENT_WIDTH / texture.width()).min(1.0);
+                    let size = texture.size() * scale;
+                    let x = MARGIN + (CONTENT_WIDTH - size.x) * 0.5;
+                    ops.push(DrawOp::Image(
+                        texture.clone(),
+                        Rect::new(x, y, size.x, size.y),
+                    ));
+                    y += size.y + BLOCK_GAP;
+                }
+                None => {
+                    let span = Span {
+                        text: format!("[missing image: {path}]"),
+                        bold: false,
+                        link: None,
+                    };
+                    y = layout_spans(&mut ops, &[span], MARGIN, y, BODY_SIZE);
+                }
+            },
+            Block::Rule => {
+                ops.push(DrawOp::Rect(
+                    Rect::new(MARGIN, y + BLOCK_GAP, CONTENT_WIDTH, 2.0),
+                    RULE_COLOR,
+                ));
+                y += 2.0 * BLOCK_GAP + 2.0;
+            }
+            Block::Blank => y += BODY_SIZE as f32 * 0.5,
+        }
+    }
+
+    (ops, (y + MARGIN).max(MIN_PAGE_HEIGHT))
+}
+
+/// Wraps the words at the right margin, returns the `y` below the last line
+fn layout_spans(ops: &mut Vec<DrawOp>, spans: &[Span], left: f32, top: f32, size: u16) -> f32 {
+    let line_height = size as f32 * LINE_SPACING;
+    let space = measure_text(" ", None, size, 1.0).width;
+    let right = MARGIN + CONTENT_WIDTH;
+
+    let words = spans.iter().flat_map(|span| {
+        span.text
+            .split_whitespace()
+            .map(move |word| (word, span.bold, &span.link))
+    });
+
+    let (mut x, mut y) = (left, top);
+    for (word, bold, link) in words {
+        let width = measure_text(word, None, size, 1.0).width;
+        if x > left && x + width > right {
+            x = left;
+            y += line_height;
+        }
+        ops.push(DrawOp::Text {
+            text: word.to_string(),
+            x,
+            y: y + size as f32,
+            size,
+            bold,
+            link: link.clone(),
+        });
+        x += width + space;
+    }
+
+    y + line_height
+}
+
+/// Area of every linked word, in pixels of the page
+fn links(ops: &[DrawOp]) -> Vec<(String, Rect)> {
+    ops.iter()
+        .filter_map(|op| match op {
+            DrawOp::Text {
+                text,
+                x,
+                y,
+                size,
+                link: Some(link),
+                ..
+            } => {
+                let width = measure_text(text, None, *size, 1.0).width;
+                let height = *size as f32 * LINE_SPACING;
+                Some((
+                    link.clone(),
+                    Rect::new(*x, *y - *size as f32, width, height),
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn render(ops: &[DrawOp], height: f32) -> Texture2D {
+    let height = height.min(MAX_PAGE_HEIGHT);
+    let target = render_target(PAGE_WIDTH as u32, height as u32);
+    target.texture.set_filter(FilterMode::Linear);
+
+    // Y grows downwards in the texture, like in the image files
+    set_camera(&Camera2D {
+        target: vec2(PAGE_WIDTH * 0.5, height * 0.5),
+        zoom: vec2(2.0 / PAGE_WIDTH, 2.0 / height),
+        render_target: Some(target.clone()),
+        ..Default::default()
+    });
+    // `clear_background` would also drop whatever was drawn to the screen this frame
+    draw_rectangle(0.0, 0.0, PAGE_WIDTH, height, WHITE);
+
+    for op in ops {
+        match op {
+            DrawOp::Text {
+                text,
+                x,
+                y,
+                size,
+                bold,
+                link,
+            } => {
+                let color = if link.is_some() {
+                    LINK_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+                draw_text(text, *x, *y, *size as f32, color);
+                // Fake bold, the default font has no bold variant
+                if *bold {
+                    draw_text(text, *x + 1.0, *y, *size as f32, color);
+                }
+                if link.is_some() {
+                    let width = measure_text(text, None, *size, 1.0).width;
+                    draw_rectangle(*x, *y + 3.0, width, 2.0, color);
+                }
+            }
+            DrawOp::Rect(rect, color) => draw_rectangle(rect.x, rect.y, rect.w, rect.h, *color),
+            DrawOp::Image(texture, rect) => draw_texture_ex(
+                texture,
+                rect.x,
+                rect.y,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(rect.size()),
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+
+    set_default_camera();
+    target.texture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str, bold: bool, link: Option<&str>) -> Span {
+        Span {
+            text: text.to_string(),
+            bold,
+            link: link.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_blocks() {
+        let source = "\
+# Title
+#### Deep heading
+First line
+second **bold** line
+
+- item with [a link](Document-0466)
+* other item
+---
+![Map](map.png)
+```
+  code stays as is
+```";
+        assert_eq!(
+            parse_markdown(source),
+            [
+                Block::Heading(1, "Title".to_string()),
+                Block::Heading(3, "Deep heading".to_string()),
+                Block::Paragraph(vec![
+                    span("First line second ", false, None),
+                    span("bold", true, None),
+                    span(" line", false, None),
+                ]),
+                Block::Blank,
+                Block::ListItem(vec![
+                    span("item with ", false, None),
+                    span("a link", false, Some("Document-0466")),
+                ]),
+                Block::ListItem(vec![span("other item", false, None)]),
+                Block::Rule,
+                Block::Image("map.png".to_string()),
+                Block::Code(vec!["  code stays as is".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_code_block_runs_to_the_end() {
+        assert_eq!(
+            parse_markdown("text\n```\n# not a heading"),
+            [
+                Block::Paragraph(vec![span("text", false, None)]),
+                Block::Code(vec!["# not a heading".to_string()]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+const MOUNTINFO_PATH: &str = "/proc/self/mountinfo";
+/// Mountinfo is re-read at least this often, in case an event was missed
+const RESCAN_INTERVAL_MS: i32 = 5000;
+const UDISKIE_RESTART_DELAY: Duration = Duration::from_secs(5);
+
+pub enum UsbEvent {
+    Mounted(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Watches the mount table and reports volumes mounted under one of the mount roots.
+/// The kernel wakes up `poll` on `/proc/self/mountinfo` whenever the table changes,
+/// so there is no need to poll the USB drive itself.
+pub struct UsbWatcher {
+    events: Receiver<UsbEvent>,
+}
+
+impl UsbWatcher {
+    /// Volumes that are already mounted are reported as `Mounted` right away.
+    /// An empty `mount_roots` accepts mounts at any path.
+    pub fn start(mount_roots: Vec<PathBuf>) -> Self {
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            if let Err(err) = watch_mounts(&mount_roots, &sender) {
+                eprintln!("USB watcher stopped: {err}");
+            }
+        });
+
+        UsbWatcher { events }
+    }
+
+    /// Reports only the events sent through the returned sender, for running without
+    /// real drives
+    pub fn scripted() -> (Self, Sender<UsbEvent>) {
+        let (sender, events) = mpsc::channel();
+        (UsbWatcher { events }, sender)
+    }
+
+    pub fn poll_events(&self) -> Vec<UsbEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+fn watch_mounts(mount_roots: &[PathBuf], sender: &Sender<UsbEvent>) -> std::io::Result<()> {
+    let mut file = File::open(MOUNTINFO_PATH)?;
+    let mut known = HashSet::new();
+
+    loop {
+        let mut content = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut content)?;
+
+        let current = parse_mountinfo(&content)
+            .into_iter()
+            .filter(|mount_point| is_under_roots(mount_point, mount_roots))
+            .collect::<HashSet<_>>();
+        for removed in known.difference(&current) {
+            if sender.send(UsbEvent::Removed(removed.clone())).is_err() {
+                return Ok(());
+            }
+        }
+        for mounted in current.difference(&known) {
+            if sender.send(UsbEvent::Mounted(mounted.clone())).is_err() {
+                return Ok(());
+            }
+        }
+        known = current;
+
+        let mut poll_fd = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLPRI,
+            revents: 0,
+        };
+        // Safety: `poll_fd` points to a single valid pollfd for the duration of the call
+        let result = unsafe { libc::poll(&mut poll_fd, 1, RESCAN_INTERVAL_MS) };
+        if result < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// Returns mount points, the fifth field of every line
+fn parse_mountinfo(content: &str) -> Vec<PathBuf> {
+    content
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(|mount_point| PathBuf::from(unescape_mount_point(mount_point)))
+        .collect()
+}
+
+/// Spaces and other special characters are escaped as octal, e.g. `\040`
+fn unescape_mount_point(escaped: &str) -> String {
+    let mut result = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            result.push(char);
+            continue;
+        }
+
+        let octal = chars.by_ref().take(3).collect::<String>();
+        match u8::from_str_radix(&octal, 8) {
+            Ok(byte) => result.push(byte as char),
+            Err(_) => {
+                result.push('\\');
+                result.push_str(&octal);
+            }
+        }
+    }
+
+    result
+}
+
+fn is_under_roots(mount_point: &Path, mount_roots: &[PathBuf]) -> bool {
+    if mount_roots.is_empty() {
+        return mount_point != Path::new("/");
+    }
+
+    mount_roots.iter().any(|root| mount_point.starts_with(root))
+}
+
+/// Keeps `udiskie` running for automounting, restarting it if it dies
+pub struct Udiskie {
+    child: Option<Child>,
+    last_start: Option<Instant>,
+    /// Set once udiskie could not be spawned, e.g. because it is not installed
+    gave_up: bool,
+}
+
+impl Udiskie {
+    /// Not started until the first call to `supervise`
+    pub fn new() -> Self {
+        Udiskie {
+            child: None,
+            last_start: None,
+            gave_up: false,
+        }
+    }
+
+    fn spawn(&mut self) -> Result<(), String> {
+        self.last_start = Some(Instant::now());
+        let child = Command::new("udiskie")
+            .arg("-a")
+            .spawn()
+            .map_err(|err| format!("Failed to spawn udiskie: {err}"))?;
+        self.child = Some(child);
+
+        Ok(())
+    }
+
+    /// Call regularly, returns a message if udiskie was (re)started or died. If it cannot be
+    /// spawned at all, that is reported once and it is not tried again.
+    pub fn supervise(&mut self) -> Option<String> {
+        if self.gave_up {
+            return None;
+        }
+        if let Some(child) = &mut self.child {
+            match child.try_wait() {
+                Ok(None) => return None,
+                Ok(Some(status)) => {
+                    self.child = None;
+                    return Some(format!("udiskie exited ({status})"));
+                }
+                Err(err) => return Some(format!("Failed to check udiskie: {err}")),
+            }
+        }
+
+        if self
+            .last_start
+            .is_some_and(|last_start| last_start.elapsed() < UDISKIE_RESTART_DELAY)
+        {
+            return None;
+        }
+        match self.spawn() {
+            Ok(()) => Some("udiskie started".to_string()),
+            Err(err) => {
+                self.gave_up = true;
+                Some(format!("{err}, not trying again"))
+            }
+        }
+    }
+}
+
+impl Default for Udiskie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Udiskie {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            // Reap it, so it does not linger as a zombie
+            let _ = child.wait();
+        }
+    }
+}
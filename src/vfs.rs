@@ -0,0 +1,510 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    document_meta::Redaction,
+    scenario::{EncryptedDocument, FileConfig, FileKind, FileLock, TerminalConfig},
+};
+
+/// File tree shared by the terminal, the document list and the document viewers, built from
+/// the scenario. Parent folders are created implicitly for every entry. The structure is
+/// read-only, only the lock state changes during the game.
+pub struct VirtualFs {
+    nodes: BTreeMap<String, VirtualFile>,
+    /// By document name
+    encrypted: HashMap<String, EncryptedDocument>,
+    state: RefCell<LockState>,
+}
+
+pub struct VirtualFile {
+    pub path: String,
+    pub kind: FileKind,
+    pub content: String,
+    /// Name of the document texture for `FileKind::Document`
+    pub document: Option<String>,
+    pub owner: String,
+    pub hidden: bool,
+    pub lock: Option<FileLock>,
+}
+
+#[derive(Default)]
+struct LockState {
+    /// Password-locked paths the players already opened
+    unlocked_paths: HashSet<String>,
+    completed_payloads: HashSet<String>,
+    /// Clock time the system was unlocked at, time locks count from here
+    unlocked_at: Option<Duration>,
+    /// How long the system was already unlocked in the game a session was resumed from
+    unlocked_before: Duration,
+    /// Names of encrypted documents the players or the game master decrypted
+    decrypted_documents: HashSet<String>,
+    /// Ids of redactions the game master revealed
+    revealed_redactions: HashSet<String>,
+}
+
+impl LockState {
+    fn unlocked_for(&self, now: Duration) -> Option<Duration> {
+        self.unlocked_at
+            .map(|at| now.saturating_sub(at) + self.unlocked_before)
+    }
+}
+
+/// Lock state saved into the session
+#[derive(Default, Serialize, Deserialize)]
+pub struct FilesystemState {
+    pub unlocked_paths: Vec<String>,
+    pub completed_payloads: Vec<String>,
+    pub unlocked_for_secs: Option<u64>,
+    #[serde(default)]
+    pub decrypted_documents: Vec<String>,
+    #[serde(default)]
+    pub revealed_redactions: Vec<String>,
+}
+
+/// Why a file cannot be opened right now
+pub enum Locked {
+    Password,
+    /// Opens once the payload is completed
+    Payload,
+    /// Opens after the remaining time
+    Time(Duration),
+}
+
+impl VirtualFile {
+    fn folder(path: String, owner: &str) -> Self {
+        VirtualFile {
+            path,
+            kind: FileKind::Folder,
+            content: String::new(),
+            document: None,
+            owner: owner.to_string(),
+            hidden: false,
+            lock: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        file_name(&self.path)
+    }
+
+    pub fn is_folder(&self) -> bool {
+        self.kind == FileKind::Folder
+    }
+}
+
+impl VirtualFs {
+    /// The terminal home exists even if there are no files in it. Entries without an owner
+    /// belong to the terminal user.
+    pub fn new(
+        files: &[FileConfig],
+        encrypted: &[EncryptedDocument],
+        terminal: &TerminalConfig,
+    ) -> Result<Self, String> {
+        let mut fs = VirtualFs {
+            nodes: BTreeMap::new(),
+            encrypted: HashMap::new(),
+            state: RefCell::new(LockState::default()),
+        };
+
+        for document in encrypted {
+            if document.key.is_none() && document.payload.is_none() {
+                return Err(format!(
+                    "encrypted document `{}` needs a `key` or a `payload`",
+                    document.document
+                ));
+            }
+            if fs
+                .encrypted
+                .insert(document.document.clone(), document.clone())
+                .is_some()
+            {
+                return Err(format!(
+                    "document `{}` is encrypted more than once",
+                    document.document
+                ));
+            }
+        }
+
+        let mut defined = HashSet::new();
+        for file in files {
+            let path = checked_path(&file.path)?;
+            if path == "/" {
+                return Err("`/` cannot be redefined".to_string());
+            }
+            if !defined.insert(path.clone()) {
+                return Err(format!("`{path}` is defined more than once"));
+            }
+            if file.kind != FileKind::Folder && fs.nodes.contains_key(&path) {
+                return Err(format!("`{path}` is both a file and a folder"));
+            }
+            if file.kind == FileKind::Document && file.document.is_none() {
+                return Err(format!("document `{path}` is missing `document`"));
+            }
+            if file.kind != FileKind::Document && file.document.is_some() {
+                return Err(format!(
+                    "`document` is only allowed for documents, see `{path}`"
+                ));
+            }
+            if file.kind == FileKind::Folder && !file.content.is_empty() {
+                return Err(format!("folder `{path}` cannot have content"));
+            }
+
+            fs.add_folder(parent(&path), &terminal.user)?;
+            // Replaces an implicit folder created by an earlier entry
+            fs.nodes.insert(
+                path.clone(),
+                VirtualFile {
+                    path,
+                    kind: file.kind,
+                    content: file.content.clone(),
+                    document: file.document.clone(),
+                    owner: file.owner.clone().unwrap_or(terminal.user.clone()),
+                    hidden: file.hidden,
+                    lock: file.lock.clone(),
+                },
+            );
+        }
+        fs.add_folder(&checked_path(&terminal.home)?, &terminal.user)?;
+
+        Ok(fs)
+    }
+
+    /// Adds the folder with all of its parents
+    fn add_folder(&mut self, path: &str, owner: &str) -> Result<(), String> {
+        let mut path = path;
+        loop {
+            match self.nodes.get(path) {
+                Some(node) if node.is_folder() => return Ok(()),
+                Some(_) => return Err(format!("`{path}` is both a file and a folder")),
+                None => {
+                    let owner = if path == "/" { "root" } else { owner };
+                    self.nodes.insert(
+                        path.to_string(),
+                        VirtualFile::folder(path.to_string(), owner),
+                    );
+                }
+            }
+            if path == "/" {
+                return Ok(());
+            }
+            path = parent(path);
+        }
+    }
+
+    /// Documents that are not placed anywhere by the scenario show up in `folder`
+    pub fn add_unplaced_documents(&mut self, names: &[String], folder: &str, owner: &str) {
+        let placed = self
+            .nodes
+            .values()
+            .filter_map(|node| node.document.clone())
+            .collect::<HashSet<_>>();
+        for name in names.iter().filter(|name| !placed.contains(*name)) {
+            let path = resolve(folder, folder, name);
+            if self.nodes.contains_key(&path) || self.add_folder(folder, owner).is_err() {
+                continue;
+            }
+            self.nodes.insert(
+                path.clone(),
+                VirtualFile {
+                    path,
+                    kind: FileKind::Document,
+                    content: String::new(),
+                    document: Some(name.clone()),
+                    owner: owner.to_string(),
+                    hidden: false,
+                    lock: None,
+                },
+            );
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&VirtualFile> {
+        self.nodes.get(path)
+    }
+
+    pub fn is_folder(&self, path: &str) -> bool {
+        self.get(path).is_some_and(VirtualFile::is_folder)
+    }
+
+    /// Folders first, both sorted by name. `None` if the folder does not exist.
+    pub fn list(&self, folder: &str, show_hidden: bool) -> Option<Vec<&VirtualFile>> {
+        if !self.is_folder(folder) {
+            return None;
+        }
+
+        let (folders, files) = self
+            .nodes
+            .values()
+            .filter(|node| node.path != "/" && parent(&node.path) == folder)
+            .filter(|node| show_hidden || !node.hidden)
+            .partition::<Vec<_>, _>(|node| node.is_folder());
+
+        Some(folders.into_iter().chain(files).collect())
+    }
+
+    /// Every document that is not hidden, including locked ones
+    pub fn documents(&self) -> Vec<&VirtualFile> {
+        self.nodes
+            .values()
+            .filter(|node| node.kind == FileKind::Document && !self.is_hidden(&node.path))
+            .collect()
+    }
+
+    /// A file is hidden if it or any of its folders is
+    fn is_hidden(&self, path: &str) -> bool {
+        ancestors(path).any(|path| self.get(path).is_some_and(|node| node.hidden))
+    }
+
+    /// Checks the file and all of its folders, returns the first locked one. `now` is the
+    /// clock time, see `Clock::now`.
+    pub fn locked(&self, path: &str, now: Duration) -> Option<(&VirtualFile, Locked)> {
+        let mut ancestors = ancestors(path).collect::<Vec<_>>();
+        ancestors.reverse();
+        ancestors
+            .into_iter()
+            .filter_map(|path| self.get(path))
+            .find_map(|node| self.lock_of(node, now).map(|locked| (node, locked)))
+    }
+
+    fn lock_of(&self, node: &VirtualFile, now: Duration) -> Option<Locked> {
+        let state = self.state.borrow();
+        match node.lock.as_ref()? {
+            FileLock::Password { .. } => {
+                (!state.unlocked_paths.contains(&node.path)).then_some(Locked::Password)
+            }
+            FileLock::Payload { payload } => {
+                (!state.completed_payloads.contains(payload)).then_some(Locked::Payload)
+            }
+            FileLock::Time { after_secs } => {
+                let after = Duration::from_secs(*after_secs);
+                match state.unlocked_for(now) {
+                    Some(elapsed) if elapsed >= after => None,
+                    Some(elapsed) => Some(Locked::Time(after - elapsed)),
+                    None => Some(Locked::Time(after)),
+                }
+            }
+        }
+    }
+
+    /// Returns `false` if the password is wrong or the path is not password-locked
+    pub fn unlock_with_password(&self, path: &str, password: &str) -> bool {
+        match self.get(path).and_then(|node| node.lock.as_ref()) {
+            Some(FileLock::Password { password: expected }) if expected == password => {
+                self.state
+                    .borrow_mut()
+                    .unlocked_paths
+                    .insert(path.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn complete_payload(&self, id: &str) {
+        self.state
+            .borrow_mut()
+            .completed_payloads
+            .insert(id.to_string());
+    }
+
+    /// Names of the encrypted documents, decrypted or not
+    pub fn encrypted_documents(&self) -> Vec<String> {
+        self.encrypted.keys().cloned().collect()
+    }
+
+    /// Encrypted documents are decrypted by their key, their payload or the game master
+    pub fn is_encrypted(&self, document: &str) -> bool {
+        let state = self.state.borrow();
+        self.encrypted.get(document).is_some_and(|encrypted| {
+            !state.decrypted_documents.contains(document)
+                && !encrypted
+                    .payload
+                    .as_ref()
+                    .is_some_and(|payload| state.completed_payloads.contains(payload))
+        })
+    }
+
+    /// Documents that are only decrypted by a payload have no key
+    pub fn has_key(&self, document: &str) -> bool {
+        self.encrypted
+            .get(document)
+            .is_some_and(|encrypted| encrypted.key.is_some())
+    }
+
+    /// Returns `false` if the key is wrong or the document cannot be decrypted with a key
+    pub fn decrypt_with_key(&self, document: &str, key: &str) -> bool {
+        let matches = self
+            .encrypted
+            .get(document)
+            .and_then(|encrypted| encrypted.key.as_deref())
+            .is_some_and(|expected| expected == key.trim());
+        if matches {
+            self.force_decrypt(document);
+        }
+
+        matches
+    }
+
+    /// Returns `false` if the document is not encrypted at all
+    pub fn force_decrypt(&self, document: &str) -> bool {
+        if !self.encrypted.contains_key(document) {
+            return false;
+        }
+        self.state
+            .borrow_mut()
+            .decrypted_documents
+            .insert(document.to_string());
+
+        true
+    }
+
+    /// Redactions are revealed by their payload or the game master
+    pub fn is_revealed(&self, redaction: &Redaction) -> bool {
+        let state = self.state.borrow();
+        state.revealed_redactions.contains(&redaction.id)
+            || redaction
+                .payload
+                .as_ref()
+                .is_some_and(|payload| state.completed_payloads.contains(payload))
+    }
+
+    pub fn reveal(&self, id: &str) {
+        self.state
+            .borrow_mut()
+            .revealed_redactions
+            .insert(id.to_string());
+    }
+
+    /// Starts the clock of time locks
+    pub fn on_system_unlocked(&self, now: Duration) {
+        self.state.borrow_mut().unlocked_at = Some(now);
+    }
+
+    /// Locks everything again for the next group
+    pub fn reset(&self) {
+        *self.state.borrow_mut() = LockState::default();
+    }
+
+    pub fn save_state(&self, now: Duration) -> FilesystemState {
+        let state = self.state.borrow();
+        FilesystemState {
+            unlocked_paths: state.unlocked_paths.iter().cloned().collect(),
+            completed_payloads: state.completed_payloads.iter().cloned().collect(),
+            unlocked_for_secs: state.unlocked_for(now).map(|elapsed| elapsed.as_secs()),
+            decrypted_documents: state.decrypted_documents.iter().cloned().collect(),
+            revealed_redactions: state.revealed_redactions.iter().cloned().collect(),
+        }
+    }
+
+    /// Time locks go on from where they were when the state was saved
+    pub fn restore_state(&self, saved: FilesystemState, now: Duration) {
+        *self.state.borrow_mut() = LockState {
+            unlocked_paths: saved.unlocked_paths.into_iter().collect(),
+            completed_payloads: saved.completed_payloads.into_iter().collect(),
+            unlocked_at: saved.unlocked_for_secs.map(|_| now),
+            unlocked_before: Duration::from_secs(saved.unlocked_for_secs.unwrap_or_default()),
+            decrypted_documents: saved.decrypted_documents.into_iter().collect(),
+            revealed_redactions: saved.revealed_redactions.into_iter().collect(),
+        };
+    }
+}
+
+/// Resolves `path` relative to `cwd`, handling `.` and `..`. `~` stands for `home`.
+pub fn resolve(cwd: &str, home: &str, path: &str) -> String {
+    let path = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("{home}{rest}"),
+        _ => path.to_string(),
+    };
+    let start = if path.starts_with('/') { "" } else { cwd };
+
+    let mut components = vec![];
+    for component in start.split('/').chain(path.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+
+    format!("/{}", components.join("/"))
+}
+
+fn checked_path(path: &str) -> Result<String, String> {
+    if !path.starts_with('/') {
+        return Err(format!("path `{path}` must be absolute"));
+    }
+    if path
+        .split('/')
+        .skip(1)
+        .any(|component| matches!(component, "." | ".."))
+    {
+        return Err(format!("path `{path}` must not contain `.` or `..`"));
+    }
+
+    Ok(resolve("/", "/", path))
+}
+
+fn parent(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(index) => &path[..index],
+    }
+}
+
+/// The path itself followed by all of its parents up to `/`
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    let mut next = Some(path);
+    std::iter::from_fn(move || {
+        let current = next?;
+        next = (current != "/").then(|| parent(current));
+        Some(current)
+    })
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relative_and_absolute_paths() {
+        assert_eq!(
+            resolve("/home/agent", "/home/agent", "notes"),
+            "/home/agent/notes"
+        );
+        assert_eq!(
+            resolve("/home/agent", "/home/agent", "/etc/passwd"),
+            "/etc/passwd"
+        );
+        assert_eq!(
+            resolve("/home/agent", "/home/agent", "./a//b/"),
+            "/home/agent/a/b"
+        );
+        assert_eq!(
+            resolve("/home/agent", "/home/agent", "../x/../y"),
+            "/home/y"
+        );
+        assert_eq!(resolve("/", "/home/agent", "../../.."), "/");
+    }
+
+    #[test]
+    fn tilde_is_the_home_folder() {
+        assert_eq!(resolve("/tmp", "/home/agent", "~"), "/home/agent");
+        assert_eq!(
+            resolve("/tmp", "/home/agent", "~/documents"),
+            "/home/agent/documents"
+        );
+        // Only `~` on its own or followed by a slash
+        assert_eq!(resolve("/tmp", "/home/agent", "~root"), "/tmp/~root");
+    }
+}
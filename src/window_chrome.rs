@@ -0,0 +1,362 @@
+use macroquad::prelude::*;
+
+use crate::{
+    context::AppContext,
+    input::InputEvent,
+    system::TOP_BAR_HEIGHT,
+    windows::{draw_window_top_bar, HEADER_HEIGHT},
+};
+
+/// Width of the border that resizes the window when dragged
+const RESIZE_BORDER: f32 = 8.0;
+const BUTTON_SIZE: f32 = 40.0;
+const BUTTON_SPACING: f32 = 8.0;
+/// Room for the close, maximize and minimize buttons at the right end of the top bar
+pub const BUTTONS_WIDTH: f32 = 3.0 * (BUTTON_SIZE + BUTTON_SPACING) + BUTTON_SPACING;
+const MAXIMIZE_ICON_SIZE: f32 = 22.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Button {
+    Minimize,
+    Maximize,
+    Close,
+}
+
+/// Edges moved by a resize
+#[derive(Clone, Copy, Default)]
+struct Edges {
+    left: bool,
+    right: bool,
+    top: bool,
+    bottom: bool,
+}
+
+impl Edges {
+    fn any(&self) -> bool {
+        self.left || self.right || self.top || self.bottom
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Drag {
+    /// From the cursor to the top-left corner
+    Move(Vec2),
+    /// From the cursor to the dragged edges
+    Resize(Edges, Vec2),
+}
+
+pub enum ChromeAction {
+    /// The event is for the content of the window
+    None,
+    /// Used for moving, resizing or maximizing, the content should ignore it
+    Handled,
+    Minimize,
+    Close,
+}
+
+/// Top bar with the close, maximize and minimize buttons, moving and resizing, shared by
+/// the windows the players can rearrange
+#[derive(Clone)]
+pub struct WindowChrome {
+    rect: Rect,
+    min_size: Vec2,
+    /// Where the window was before it was maximized
+    restore_rect: Option<Rect>,
+    drag: Option<Drag>,
+    /// Windows that cannot be opened again only get minimized
+    can_close: bool,
+}
+
+impl WindowChrome {
+    pub fn new(position: Vec2, size: Vec2, min_size: Vec2) -> Self {
+        let size = size.max(min_size);
+        WindowChrome {
+            rect: Rect::new(
+                position.x - size.x * 0.5,
+                position.y - size.y * 0.5,
+                size.x,
+                size.y,
+            ),
+            min_size,
+            restore_rect: None,
+            drag: None,
+            can_close: true,
+        }
+    }
+
+    pub fn without_close_button(self) -> Self {
+        WindowChrome {
+            can_close: false,
+            ..self
+        }
+    }
+
+    /// Center of the window
+    pub fn position(&self) -> Vec2 {
+        self.rect.center()
+    }
+
+    pub fn set_position(&mut self, position: Vec2) {
+        self.rect.move_to(position - self.rect.size() * 0.5);
+    }
+
+    pub fn top_left(&self) -> Vec2 {
+        self.rect.point()
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.rect.size()
+    }
+
+    /// Keeps the top-left corner in place
+    pub fn set_size(&mut self, size: Vec2) {
+        let size = size.max(self.min_size);
+        self.rect.w = size.x;
+        self.rect.h = size.y;
+    }
+
+    /// Part of the window below the top bar
+    pub fn content_rect(&self) -> Rect {
+        Rect::new(
+            self.rect.x,
+            self.rect.y + HEADER_HEIGHT,
+            self.rect.w,
+            self.rect.h - HEADER_HEIGHT,
+        )
+    }
+
+    pub fn contains_pos(&self, pos: Vec2) -> bool {
+        self.rect.contains(pos)
+    }
+
+    pub fn draw(&self, context: &AppContext, title: &str, is_focused: bool) {
+        let theme = &context.theme;
+        let header = Rect::new(self.rect.x, self.rect.y, self.rect.w, HEADER_HEIGHT);
+        draw_window_top_bar(context, title, 30.0, header, is_focused);
+
+        for (button, rect) in self.buttons() {
+            let center = rect.center();
+            match button {
+                Button::Minimize => draw_icon(context, context.textures.minimize(), center),
+                Button::Close => draw_icon(context, context.textures.close(), center),
+                // Two overlapping windows restore, one big window maximizes
+                Button::Maximize if self.restore_rect.is_some() => {
+                    let size = MAXIMIZE_ICON_SIZE * 0.75;
+                    let offset = MAXIMIZE_ICON_SIZE * 0.25;
+                    let back =
+                        center - vec2(MAXIMIZE_ICON_SIZE * 0.5 - offset, MAXIMIZE_ICON_SIZE * 0.5);
+                    context.renderer.draw_rectangle_lines(
+                        back.x,
+                        back.y,
+                        size,
+                        size,
+                        3.0,
+                        theme.foreground,
+                    );
+                    let front =
+                        center - vec2(MAXIMIZE_ICON_SIZE * 0.5, MAXIMIZE_ICON_SIZE * 0.5 - offset);
+                    context
+                        .renderer
+                        .draw_rectangle(front.x, front.y, size, size, theme.background);
+                    context.renderer.draw_rectangle_lines(
+                        front.x,
+                        front.y,
+                        size,
+                        size,
+                        3.0,
+                        theme.foreground,
+                    );
+                }
+                Button::Maximize => {
+                    let tl = center - Vec2::splat(MAXIMIZE_ICON_SIZE * 0.5);
+                    context.renderer.draw_rectangle_lines(
+                        tl.x,
+                        tl.y,
+                        MAXIMIZE_ICON_SIZE,
+                        MAXIMIZE_ICON_SIZE,
+                        3.0,
+                        theme.foreground,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Has to be called every frame
+    pub fn update(&mut self, context: &AppContext) {
+        // Follows the screen size
+        if self.restore_rect.is_some() {
+            self.rect = maximized_rect(context.renderer.screen_size());
+        }
+    }
+
+    /// Has to be called with every event of the window before the content gets it
+    pub fn handle_input(&mut self, context: &AppContext, event: &InputEvent) -> ChromeAction {
+        let screen = context.renderer.screen_size();
+        match *event {
+            InputEvent::MouseDown {
+                button: MouseButton::Left,
+                pos,
+                ..
+            } => self.press(pos, screen),
+            InputEvent::DoubleClick { pos } if self.is_in_header(pos) => {
+                if !self.buttons().iter().any(|(_, rect)| rect.contains(pos)) {
+                    self.toggle_maximized(screen);
+                }
+                ChromeAction::Handled
+            }
+            InputEvent::MouseUp {
+                button: MouseButton::Left,
+                ..
+            } => match self.drag.take() {
+                Some(_) => ChromeAction::Handled,
+                None => ChromeAction::None,
+            },
+            InputEvent::Drag {
+                button: MouseButton::Left,
+                pos,
+                ..
+            } => match self.drag {
+                Some(Drag::Move(offset)) => {
+                    self.rect.move_to(pos + offset);
+                    ChromeAction::Handled
+                }
+                Some(Drag::Resize(edges, offset)) => {
+                    self.resize(edges, pos + offset);
+                    ChromeAction::Handled
+                }
+                None => ChromeAction::None,
+            },
+            _ => ChromeAction::None,
+        }
+    }
+
+    fn press(&mut self, pos: Vec2, screen: Vec2) -> ChromeAction {
+        self.drag = None;
+        let button = self
+            .buttons()
+            .into_iter()
+            .find(|(_, rect)| rect.contains(pos));
+        match button {
+            Some((Button::Minimize, _)) => return ChromeAction::Minimize,
+            Some((Button::Close, _)) => return ChromeAction::Close,
+            Some((Button::Maximize, _)) => {
+                self.toggle_maximized(screen);
+                return ChromeAction::Handled;
+            }
+            None => {}
+        }
+
+        let edges = self.edges_at(pos);
+        if edges.any() {
+            let offset = vec2(
+                match (edges.left, edges.right) {
+                    (true, _) => self.rect.left() - pos.x,
+                    (_, true) => self.rect.right() - pos.x,
+                    _ => 0.0,
+                },
+                match (edges.top, edges.bottom) {
+                    (true, _) => self.rect.top() - pos.y,
+                    (_, true) => self.rect.bottom() - pos.y,
+                    _ => 0.0,
+                },
+            );
+            self.drag = Some(Drag::Resize(edges, offset));
+            return ChromeAction::Handled;
+        }
+
+        if self.is_in_header(pos) {
+            // A maximized window stays where it is
+            if self.restore_rect.is_none() {
+                self.drag = Some(Drag::Move(self.rect.point() - pos));
+            }
+            return ChromeAction::Handled;
+        }
+
+        ChromeAction::None
+    }
+
+    fn is_in_header(&self, pos: Vec2) -> bool {
+        pos.y < self.rect.y + HEADER_HEIGHT && self.rect.contains(pos)
+    }
+
+    /// Moves the dragged edges to `pos`, without getting smaller than the minimum size
+    fn resize(&mut self, edges: Edges, pos: Vec2) {
+        let rect = &mut self.rect;
+        if edges.left {
+            let right = rect.right();
+            rect.x = pos.x.min(right - self.min_size.x);
+            rect.w = right - rect.x;
+        } else if edges.right {
+            rect.w = (pos.x - rect.x).max(self.min_size.x);
+        }
+        if edges.top {
+            let bottom = rect.bottom();
+            rect.y = pos.y.min(bottom - self.min_size.y);
+            rect.h = bottom - rect.y;
+        } else if edges.bottom {
+            rect.h = (pos.y - rect.y).max(self.min_size.y);
+        }
+    }
+
+    fn toggle_maximized(&mut self, screen: Vec2) {
+        self.drag = None;
+        match self.restore_rect.take() {
+            Some(rect) => self.rect = rect,
+            None => {
+                self.restore_rect = Some(self.rect);
+                self.rect = maximized_rect(screen);
+            }
+        }
+    }
+
+    /// Edges of the window close to `pos`, none while maximized
+    fn edges_at(&self, pos: Vec2) -> Edges {
+        if self.restore_rect.is_some() || !self.rect.contains(pos) {
+            return Edges::default();
+        }
+
+        Edges {
+            left: pos.x < self.rect.left() + RESIZE_BORDER,
+            right: pos.x > self.rect.right() - RESIZE_BORDER,
+            top: pos.y < self.rect.top() + RESIZE_BORDER,
+            bottom: pos.y > self.rect.bottom() - RESIZE_BORDER,
+        }
+    }
+
+    /// From right to left: close, maximize and minimize
+    fn buttons(&self) -> Vec<(Button, Rect)> {
+        let buttons = match self.can_close {
+            true => vec![Button::Close, Button::Maximize, Button::Minimize],
+            false => vec![Button::Maximize, Button::Minimize],
+        };
+
+        let y = self.rect.y + (HEADER_HEIGHT - BUTTON_SIZE) * 0.5;
+        buttons
+            .into_iter()
+            .enumerate()
+            .map(|(index, button)| {
+                let x = self.rect.right() - (index + 1) as f32 * (BUTTON_SIZE + BUTTON_SPACING);
+                (button, Rect::new(x, y, BUTTON_SIZE, BUTTON_SIZE))
+            })
+            .collect()
+    }
+}
+
+/// The whole screen below the top bar
+fn maximized_rect(screen: Vec2) -> Rect {
+    Rect::new(0.0, TOP_BAR_HEIGHT, screen.x, screen.y - TOP_BAR_HEIGHT)
+}
+
+fn draw_icon(context: &AppContext, texture: Option<Texture2D>, center: Vec2) {
+    let Some(texture) = texture else {
+        return;
+    };
+    let size = texture.size();
+    context.renderer.draw_texture(
+        &texture,
+        center.x - size.x * 0.5,
+        center.y - size.y * 0.5,
+        context.theme.background,
+    );
+}
@@ -0,0 +1,155 @@
+use macroquad::prelude::*;
+use serde::Serialize;
+
+use crate::windows::Window;
+
+/// Stays the same while the window is open, unlike its place in the stack
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(transparent)]
+pub struct WindowId(u64);
+
+/// Open windows ordered from the bottom to the top of the screen
+///
+/// The topmost visible window is the focused one, it is the only one that gets the keyboard.
+pub struct WindowManager {
+    windows: Vec<(WindowId, Box<dyn Window>)>,
+    next_id: u64,
+    focused: Option<WindowId>,
+}
+
+impl WindowManager {
+    pub fn new() -> Self {
+        WindowManager {
+            windows: vec![],
+            next_id: 0,
+            focused: None,
+        }
+    }
+
+    /// New windows open on top of the others
+    pub fn push(&mut self, window: Box<dyn Window>) -> WindowId {
+        let id = WindowId(self.next_id);
+        self.next_id += 1;
+        self.windows.push((id, window));
+        self.update_focus();
+        id
+    }
+
+    pub fn remove(&mut self, id: WindowId) -> Option<Box<dyn Window>> {
+        let index = self.index_of(id)?;
+        let (_, window) = self.windows.remove(index);
+        if self.focused == Some(id) {
+            self.focused = None;
+        }
+        self.update_focus();
+        Some(window)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&dyn Window) -> bool) {
+        self.windows.retain(|(_, window)| keep(window.as_ref()));
+        if self.focused.is_some_and(|id| self.index_of(id).is_none()) {
+            self.focused = None;
+        }
+        self.update_focus();
+    }
+
+    pub fn clear(&mut self) {
+        self.windows.clear();
+        self.focused = None;
+    }
+
+    pub fn get_mut(&mut self, id: WindowId) -> Option<&mut Box<dyn Window>> {
+        let index = self.index_of(id)?;
+        Some(&mut self.windows[index].1)
+    }
+
+    /// Bottom to top, the order they are drawn in
+    pub fn iter(&self) -> impl Iterator<Item = (WindowId, &dyn Window)> {
+        self.windows
+            .iter()
+            .map(|(id, window)| (*id, window.as_ref()))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn Window>> {
+        self.windows.iter_mut().map(|(_, window)| window)
+    }
+
+    /// Bottom to top
+    pub fn ids(&self) -> Vec<WindowId> {
+        self.windows.iter().map(|(id, _)| *id).collect()
+    }
+
+    pub fn focused(&self) -> Option<WindowId> {
+        self.focused
+    }
+
+    /// Topmost visible window under the position
+    pub fn window_at(&self, pos: Vec2) -> Option<WindowId> {
+        self.windows
+            .iter()
+            .rev()
+            .find(|(_, window)| window.is_visible() && window.contains_pos(pos))
+            .map(|(id, _)| *id)
+    }
+
+    /// Shows the window on top of all others and focuses it
+    pub fn raise(&mut self, id: WindowId) {
+        let Some(index) = self.index_of(id) else {
+            return;
+        };
+        let entry = self.windows.remove(index);
+        self.windows.push(entry);
+        self.windows.last_mut().unwrap().1.set_visibility(true);
+        self.update_focus();
+    }
+
+    /// The focus moves to the next visible window below
+    pub fn hide(&mut self, id: WindowId) {
+        if let Some(window) = self.get_mut(id) {
+            window.set_visibility(false);
+        }
+        self.update_focus();
+    }
+
+    /// Moves the focus to the topmost visible window, needed after a window changed its
+    /// visibility on its own
+    pub fn update_focus(&mut self) {
+        let top = self
+            .windows
+            .iter()
+            .rev()
+            .find(|(_, window)| window.is_visible())
+            .map(|(id, _)| *id);
+        if top == self.focused {
+            return;
+        }
+
+        if let Some(window) = self.focused.and_then(|id| self.get_mut(id)) {
+            window.set_focused(false);
+        }
+        if let Some(window) = top.and_then(|id| self.get_mut(id)) {
+            window.set_focused(true);
+        }
+        self.focused = top;
+    }
+
+    fn index_of(&self, id: WindowId) -> Option<usize> {
+        self.windows.iter().position(|(other, _)| *other == id)
+    }
+}
+
+impl Default for WindowManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<Box<dyn Window>> for WindowManager {
+    fn from_iter<T: IntoIterator<Item = Box<dyn Window>>>(iter: T) -> Self {
+        let mut manager = WindowManager::new();
+        for window in iter {
+            manager.push(window);
+        }
+        manager
+    }
+}
@@ -1,38 +1,73 @@
 use macroquad::prelude::*;
+use serde::Serialize;
 
-use crate::system::{texture_storage, BG_COLOR, TEXTURE_STORAGE};
+use crate::context::AppContext;
+use crate::input::InputEvent;
+use crate::session::WindowSnapshot;
 
 pub const HEADER_HEIGHT: f32 = 70.0;
 
 pub trait Window {
-    async fn new_boxed() -> Box<dyn Window>
-    where
-        Self: Sized;
-
     fn position(&self) -> Vec2;
 
     fn top_left(&self) -> Vec2;
 
     fn size(&self) -> Vec2;
 
-    fn draw(&mut self);
+    fn draw(&mut self, context: &AppContext);
 
     fn is_visible(&self) -> bool;
 
     fn set_visibility(&mut self, value: bool);
 
-    fn handle_input(&mut self, event: InputEvent) -> WindowReturnAction;
+    /// Called every frame while the window is visible, before its input events
+    fn update(&mut self, _context: &AppContext) -> WindowReturnAction {
+        WindowReturnAction::None
+    }
 
-    fn icon(&self) -> Option<Texture2D>;
+    /// Pointer events under the window or captured by it, keyboard events if it has the focus
+    fn handle_input(&mut self, context: &AppContext, event: InputEvent) -> WindowReturnAction;
 
-    fn contains_pos(&self, pos: Vec2) -> bool;
+    fn icon(&self, context: &AppContext) -> Option<Texture2D>;
+
+    fn kind(&self) -> WindowKind;
+
+    /// Human readable name, used for example by the control server
+    fn title(&self) -> String;
+
+    /// State saved into the session file, `None` for windows that should not be restored
+    fn snapshot(&self) -> Option<WindowSnapshot>;
 
-    fn is_pos_in_header(&self, pos: Vec2) -> bool {
-        pos.x > self.top_left().x
-            && pos.x < self.top_left().x + self.size().x
-            && pos.y > self.top_left().y
-            && pos.y < self.top_left().y + HEADER_HEIGHT
+    /// Freezes windows that run on their own, like the minigame
+    fn set_paused(&mut self, _paused: bool) {}
+
+    /// Starts windows that run on their own over
+    fn restart(&mut self) {}
+
+    /// Name of the document a document window shows
+    fn document_name(&self) -> Option<&str> {
+        None
     }
+
+    /// Shows another document in a document window, the current one goes into its history
+    fn navigate_to(&mut self, _context: &AppContext, _document: String) {}
+
+    /// The focused window gets the keyboard events
+    fn set_focused(&mut self, _focused: bool) {}
+
+    fn contains_pos(&self, pos: Vec2) -> bool;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+pub enum WindowKind {
+    Login,
+    PopUp,
+    MiniGame,
+    Document,
+    DocumentList,
+    ResumePrompt,
+    HackConsole,
+    Terminal,
 }
 
 pub enum WindowReturnAction {
@@ -41,59 +76,40 @@ pub enum WindowReturnAction {
     Close,
     NewWindow(Box<dyn Window>),
     HackCompleted,
-}
-
-#[derive(Copy, Clone)]
-pub enum InputEvent {
-    None,
-    /// Position and if it is being held
-    LeftMouse(Vec2, bool),
-    Scroll(f32),
-}
-
-pub fn draw_outlined_box(
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-    thickness: f32,
-    background_color: Color,
-    outline_color: Color,
-) {
-    draw_rectangle(x, y, width, height, background_color);
-    draw_rectangle_lines(x, y, width, height, thickness, outline_color);
+    /// A minigame started by a puzzle payload was solved
+    PuzzleSolved(String),
+    ResumeSession,
+    OpenDocument(String),
+    StartMiniGame,
+    /// Name of the encrypted document and if the key was right
+    DecryptAttempt(String, bool),
+    /// Target of a clicked link and if it should open in a new window
+    FollowLink(String, bool),
 }
 
 pub fn draw_window_top_bar(
+    context: &AppContext,
     text: &str,
     font_size: f32,
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-    fg_color: Color,
-    bg_color: Color,
+    rect: Rect,
+    is_focused: bool,
 ) {
-    draw_outlined_box(x, y, width, height, 5.0, bg_color, fg_color);
-    let measure = measure_text(text, None, font_size as u16, 1.0);
-    draw_text(
+    let theme = &context.theme;
+    // Windows in the background get a greyed out header
+    let (text_color, bg_color) = if is_focused {
+        (theme.foreground, theme.background)
+    } else {
+        (theme.unfocused_text, theme.unfocused_header)
+    };
+    context
+        .renderer
+        .draw_outlined_box(rect, 5.0, bg_color, theme.foreground);
+    let measure = context.renderer.measure_text(text, font_size as u16, 1.0);
+    context.renderer.draw_text(
         text,
-        x + width * 0.5 - measure.width * 0.5,
-        y + height * 0.5 + measure.height * 0.5,
+        rect.x + rect.w * 0.5 - measure.width * 0.5,
+        rect.y + rect.h * 0.5 + measure.height * 0.5,
         font_size,
-        fg_color,
-    );
-}
-
-pub fn minimize_button(position: Vec2) -> Vec2 {
-    let texture = texture_storage().minimize().unwrap();
-    let size = texture.size();
-    draw_texture(
-        &texture,
-        position.x - size.x * 0.5,
-        position.y - size.y * 0.5,
-        BG_COLOR,
+        text_color,
     );
-
-    size
 }
@@ -0,0 +1,763 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use esc_terminal::{
+    control::{ControlCommand, StatusReport, WindowReport},
+    document_meta::DocumentMeta,
+    headless::Simulation,
+    input::Modifiers,
+    payload,
+    system::{Page, TextureStorage},
+    text_document::{MIN_PAGE_HEIGHT, PAGE_WIDTH},
+    windows::{WindowKind, HEADER_HEIGHT},
+};
+use macroquad::prelude::*;
+
+const KEY: &str = "test-key";
+
+/// The player starts in the top-left corner next to a wall, the only password piece is
+/// below it and the finish below that
+const MAP: &str = "
+OOOOOOOOOOOOOOOOOOOO
+OPO________________O
+O__________________O
+O__________________O
+OX_________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+OOOOOOOOOOOOOOOOOOOO
+";
+
+/// Running right without steering picks up the password piece and reaches the finish
+/// behind it
+const PUZZLE_MAP: &str = "
+OOOOOOOOOOOOOOOOOOOO
+OPX________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+O__________________O
+OOOOOOOOOOOOOOOOOOOO
+";
+
+const HACK_SCRIPT: &str = r#"
+id: hack
+print("Connecting...");
+progress("Decrypting", 500);
+start_minigame();
+"#;
+
+const PUZZLE: &str = "id: puzzle\n";
+
+const LEAK: &str = "id: leak\n";
+
+/// Name and sidecar of the documents, every link covers the whole page
+const DOCUMENTS: [(&str, &str); 4] = [
+    (
+        "report",
+        r#"
+title = "Report"
+links = [{ target = "appendix", rect = [0, 0, 850, 1100] }]
+
+[[redactions]]
+id = "name"
+rect = [100, 100, 200, 30]
+payload = "leak"
+
+[[redactions]]
+id = "address"
+rect = [100, 200, 200, 30]
+"#,
+    ),
+    (
+        "appendix",
+        r#"
+title = "Appendix"
+links = [{ target = "report", rect = [0, 0, 850, 1100] }]
+"#,
+    ),
+    (
+        "vault",
+        r#"
+title = "Vault"
+links = [{ target = "appendix", rect = [0, 0, 850, 1100] }]
+"#,
+    ),
+    ("intel", "title = \"Intel\"\n"),
+];
+
+/// Scenario, key and USB drive of a test in their own temporary folder
+struct Room {
+    dir: PathBuf,
+}
+
+impl Room {
+    fn new(name: &str, on_usb_removed: &str) -> Self {
+        Self::create(name, on_usb_removed, false)
+    }
+
+    /// Saves the session every 100 ms
+    fn with_sessions(name: &str) -> Self {
+        Self::create(name, "abort", true)
+    }
+
+    fn create(name: &str, on_usb_removed: &str, sessions: bool) -> Self {
+        let dir = std::env::temp_dir().join(format!("esc_terminal_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("hack.key"), KEY).unwrap();
+
+        let scenario = format!(
+            r#"
+[usb]
+key_file = "{key_file}"
+mount_roots = []
+udiskie = false
+
+[hack]
+completed_text = "Hack completed!"
+invalid_payload_text = "Invalid payload!"
+on_usb_removed = "{on_usb_removed}"
+connection_lost_text = "Connection lost!"
+
+[login]
+error_text = "Login is disabled!"
+
+[minigame]
+keys_total = 1
+move_interval_ms = 100
+finish = [1, 6]
+map = """{MAP}"""
+
+[control]
+enabled = false
+
+[session]
+enabled = {sessions}
+save_interval_ms = 100
+path = "{session_file}"
+
+[[payloads]]
+id = "hack"
+file = "secret.hack"
+action = {{ type = "hack" }}
+
+[[payloads]]
+id = "leak"
+file = "leak.hack"
+action = {{ type = "message", text = "Leaked!" }}
+
+[[payloads]]
+id = "puzzle"
+file = "puzzle.hack"
+
+[payloads.action]
+type = "puzzle"
+
+[payloads.action.minigame]
+keys_total = 1
+move_interval_ms = 500
+finish = [3, 1]
+map = """{PUZZLE_MAP}"""
+
+[[files]]
+path = "/home/agent/intel"
+type = "document"
+document = "intel"
+lock = {{ type = "time", after_secs = 30 }}
+
+[[encrypted]]
+document = "vault"
+key = "open sesame"
+"#,
+            key_file = dir.join("hack.key").display(),
+            session_file = dir.join("session.json").display(),
+        );
+        fs::write(dir.join("scenario.toml"), scenario).unwrap();
+
+        Room { dir }
+    }
+
+    fn simulation(&self) -> Simulation {
+        match Simulation::new(&self.dir.join("scenario.toml")) {
+            Ok(simulation) => simulation,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// With the `DOCUMENTS` as blank pages
+    fn simulation_with_documents(&self) -> Simulation {
+        let mut documents = TextureStorage::default();
+        for (name, sidecar) in DOCUMENTS {
+            let path = self.dir.join(format!("{name}.toml"));
+            fs::write(&path, sidecar).unwrap();
+            let meta = DocumentMeta::load(&path).unwrap();
+            let page = Page::blank(vec2(PAGE_WIDTH, MIN_PAGE_HEIGHT));
+            documents.add_document(name.to_string(), vec![page], meta);
+        }
+
+        match Simulation::with_documents(&self.dir.join("scenario.toml"), documents) {
+            Ok(simulation) => simulation,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Folder standing in for a mounted drive with a single file on it
+    fn drive(&self, name: &str, file: &str, content: &str) -> PathBuf {
+        let volume = self.dir.join(name);
+        fs::create_dir_all(&volume).unwrap();
+        fs::write(volume.join(file), content).unwrap();
+        volume
+    }
+
+    fn usb_drive(&self, content: &str) -> PathBuf {
+        self.drive("usb", "secret.hack", content)
+    }
+
+    fn signed_drive(&self) -> PathBuf {
+        self.usb_drive(&payload::sign(HACK_SCRIPT, KEY.as_bytes()))
+    }
+
+    fn puzzle_drive(&self) -> PathBuf {
+        self.drive(
+            "puzzle",
+            "puzzle.hack",
+            &payload::sign(PUZZLE, KEY.as_bytes()),
+        )
+    }
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn kinds(status: &StatusReport) -> Vec<WindowKind> {
+    status.windows.iter().map(|window| window.kind).collect()
+}
+
+fn has_window(status: &StatusReport, kind: WindowKind) -> bool {
+    status.windows.iter().any(|window| window.kind == kind)
+}
+
+fn titles(status: &StatusReport) -> Vec<&str> {
+    status
+        .windows
+        .iter()
+        .map(|window| window.title.as_str())
+        .collect()
+}
+
+fn rect(window: &WindowReport) -> Rect {
+    let (x, y) = window.top_left;
+    let (w, h) = window.size;
+    Rect::new(x, y, w, h)
+}
+
+/// On the page of a document window, away from the edges where it is resized
+fn on_page(window: &WindowReport) -> Vec2 {
+    rect(window).point() + vec2(100.0, HEADER_HEIGHT + 100.0)
+}
+
+/// The history buttons are the first ones in the top bar of a document window
+fn back_button(window: &WindowReport) -> Vec2 {
+    rect(window).point() + vec2(28.0, HEADER_HEIGHT * 0.5)
+}
+
+fn forward_button(window: &WindowReport) -> Vec2 {
+    back_button(window) + vec2(48.0, 0.0)
+}
+
+/// A point of the window that no window above it covers
+fn uncovered(status: &StatusReport, index: usize) -> Vec2 {
+    let window = rect(&status.windows[index]);
+    let above = status.windows[index + 1..]
+        .iter()
+        .map(rect)
+        .collect::<Vec<_>>();
+    (1..10)
+        .flat_map(|row| (1..10).map(move |col| (col, row)))
+        .map(|(col, row)| window.point() + window.size() * vec2(col as f32, row as f32) * 0.1)
+        .find(|pos| !above.iter().any(|rect| rect.contains(*pos)))
+        .expect("the window is covered")
+}
+
+/// Plugs in the drive and waits for the hack script to start the minigame
+fn start_minigame(simulation: &mut Simulation, volume: &Path) {
+    simulation.plug_usb(volume);
+    simulation.step();
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "USBOpened");
+    assert!(has_window(&status, WindowKind::HackConsole));
+
+    assert!(simulation.run_until(Duration::from_secs(5), |status| {
+        has_window(status, WindowKind::MiniGame)
+    }));
+    assert_eq!(simulation.status().hack_status, "Minigame");
+}
+
+#[test]
+fn full_room() {
+    let room = Room::new("full_room", "abort");
+    let mut simulation = room.simulation();
+    simulation.run_for(Duration::from_millis(500));
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "NoUSB");
+    assert!(!status.is_unlocked);
+    assert!(status.windows.is_empty());
+
+    start_minigame(&mut simulation, &room.signed_drive());
+
+    // Down to the password piece, the finish appears below it
+    simulation.press_key(KeyCode::Down);
+    assert!(simulation.run_until(Duration::from_secs(5), |status| status.is_unlocked));
+
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "Completed");
+    assert_eq!(
+        kinds(&status),
+        [
+            WindowKind::DocumentList,
+            WindowKind::Terminal,
+            WindowKind::PopUp
+        ]
+    );
+    let popup = status.windows.last().unwrap();
+    assert_eq!(popup.title, "Hack completed!");
+    assert!(popup.is_focused);
+}
+
+#[test]
+fn clicking_the_minigame_keeps_its_keyboard() {
+    let room = Room::new("minigame_click", "abort");
+    let mut simulation = room.simulation();
+    start_minigame(&mut simulation, &room.signed_drive());
+
+    // On both the minigame and the hack console below it
+    simulation.click(vec2(800.0, 500.0));
+    simulation.step();
+    let status = simulation.status();
+    assert_eq!(
+        kinds(&status),
+        [WindowKind::HackConsole, WindowKind::MiniGame]
+    );
+    assert!(status.windows[1].is_focused);
+
+    simulation.press_key(KeyCode::Down);
+    assert!(simulation.run_until(Duration::from_secs(5), |status| status.is_unlocked));
+}
+
+#[test]
+fn minigame_restarts_on_crash() {
+    let room = Room::new("minigame_crash", "abort");
+    let mut simulation = room.simulation();
+    start_minigame(&mut simulation, &room.signed_drive());
+
+    // Without steering the player keeps running into the wall next to the start
+    simulation.run_for(Duration::from_secs(5));
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "Minigame");
+    assert!(!status.is_unlocked);
+}
+
+#[test]
+fn solving_a_puzzle_does_not_unlock_the_room() {
+    let room = Room::new("puzzle", "abort");
+    let mut simulation = room.simulation();
+    simulation.plug_usb(&room.puzzle_drive());
+    simulation.step();
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "NoUSB");
+    assert_eq!(kinds(&status), [WindowKind::MiniGame]);
+
+    assert!(simulation.run_until(Duration::from_secs(5), |status| {
+        status.windows.is_empty()
+    }));
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "NoUSB");
+    assert!(!status.is_unlocked);
+
+    // The hack still has to be done
+    start_minigame(&mut simulation, &room.signed_drive());
+}
+
+#[test]
+fn puzzle_is_ignored_during_the_hack() {
+    let room = Room::new("puzzle_during_hack", "abort");
+    let mut simulation = room.simulation();
+    let volume = room.signed_drive();
+    start_minigame(&mut simulation, &volume);
+
+    simulation.plug_usb(&room.puzzle_drive());
+    simulation.step();
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "Minigame");
+    assert_eq!(
+        kinds(&status),
+        [WindowKind::HackConsole, WindowKind::MiniGame]
+    );
+
+    // The hack still belongs to its own drive
+    simulation.unplug_usb(&volume);
+    simulation.step();
+    assert_eq!(simulation.status().hack_status, "NoUSB");
+}
+
+#[test]
+fn unsigned_payload_is_rejected() {
+    let room = Room::new("unsigned_payload", "abort");
+    let mut simulation = room.simulation();
+    simulation.plug_usb(&room.usb_drive(HACK_SCRIPT));
+    simulation.step();
+
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "NoUSB");
+    assert_eq!(kinds(&status), [WindowKind::PopUp]);
+    assert_eq!(status.windows[0].title, "Invalid payload!");
+}
+
+#[test]
+fn removing_the_drive_aborts_the_hack() {
+    let room = Room::new("usb_removed", "abort");
+    let mut simulation = room.simulation();
+    let volume = room.signed_drive();
+    start_minigame(&mut simulation, &volume);
+
+    simulation.unplug_usb(&volume);
+    simulation.step();
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "NoUSB");
+    assert_eq!(kinds(&status), [WindowKind::PopUp]);
+    assert_eq!(status.windows[0].title, "Connection lost!");
+
+    // The same drive starts the hack again
+    start_minigame(&mut simulation, &volume);
+}
+
+#[test]
+fn removing_the_drive_pauses_the_hack() {
+    let room = Room::new("usb_paused", "pause");
+    let mut simulation = room.simulation();
+    let volume = room.signed_drive();
+    start_minigame(&mut simulation, &volume);
+    simulation.unplug_usb(&volume);
+
+    // The minigame is frozen while the drive is out, the player does not crash
+    simulation.run_for(Duration::from_secs(5));
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "Minigame");
+    assert_eq!(
+        kinds(&status),
+        [WindowKind::HackConsole, WindowKind::MiniGame]
+    );
+
+    // Plugging the drive back in resumes instead of firing the payload again
+    simulation.plug_usb(&volume);
+    simulation.step();
+    assert_eq!(simulation.status().hack_status, "Minigame");
+    simulation.press_key(KeyCode::Down);
+    assert!(simulation.run_until(Duration::from_secs(5), |status| status.is_unlocked));
+}
+
+/// Starts the room again from the session saved by the previous simulation
+fn resume(room: &Room, simulation: Simulation) -> Simulation {
+    drop(simulation);
+    continue_session(room.simulation())
+}
+
+/// Answers the resume prompt of a new simulation
+fn continue_session(mut simulation: Simulation) -> Simulation {
+    simulation.step();
+    assert_eq!(kinds(&simulation.status()), [WindowKind::ResumePrompt]);
+
+    // The continue button
+    let center = simulation.screen_size() * 0.5;
+    simulation.click(center + vec2(-170.0, 65.0));
+    simulation.step();
+    simulation
+}
+
+#[test]
+fn resumed_hack_keeps_its_drive() {
+    let room = Room::with_sessions("resume_hack");
+    let mut simulation = room.simulation();
+    let volume = room.signed_drive();
+    start_minigame(&mut simulation, &volume);
+    simulation.run_for(Duration::from_millis(200));
+
+    let mut simulation = resume(&room, simulation);
+    assert_eq!(simulation.status().hack_status, "Minigame");
+    // The watcher reports the drive that is still plugged in
+    simulation.plug_usb(&volume);
+    simulation.step();
+    assert_eq!(simulation.status().hack_status, "Minigame");
+
+    simulation.unplug_usb(&volume);
+    simulation.step();
+    let status = simulation.status();
+    assert_eq!(status.hack_status, "NoUSB");
+    assert_eq!(status.windows.last().unwrap().title, "Connection lost!");
+}
+
+#[test]
+fn resumed_puzzle_keeps_its_map() {
+    let room = Room::with_sessions("resume_puzzle");
+    let mut simulation = room.simulation();
+    // Unlocking closes running minigames, so the puzzle comes after it
+    simulation.press_key(KeyCode::Home);
+    simulation.step();
+    simulation.plug_usb(&room.puzzle_drive());
+    simulation.run_for(Duration::from_millis(200));
+    assert!(has_window(&simulation.status(), WindowKind::MiniGame));
+
+    // On the map of the hack the player would run into the wall
+    let mut simulation = resume(&room, simulation);
+    let status = simulation.status();
+    assert!(status.is_unlocked);
+    assert!(has_window(&status, WindowKind::MiniGame));
+    assert!(simulation.run_until(Duration::from_secs(5), |status| {
+        !has_window(status, WindowKind::MiniGame)
+    }));
+}
+
+#[test]
+fn resumed_window_keeps_its_place_and_size() {
+    let room = Room::with_sessions("resume_window");
+    let mut simulation = room.simulation_with_documents();
+    simulation.control(ControlCommand::Unlock);
+    simulation.control(ControlCommand::OpenDocument("report".to_string()));
+    simulation.step();
+
+    // Moved by the top bar, then made bigger by the bottom-right corner
+    let window = rect(simulation.status().windows.last().unwrap());
+    let header = window.point() + vec2(300.0, HEADER_HEIGHT * 0.5);
+    simulation.drag(header, header - vec2(800.0, 0.0));
+    simulation.step();
+    let window = rect(simulation.status().windows.last().unwrap());
+    let corner = window.point() + window.size() - Vec2::splat(2.0);
+    simulation.drag(corner, corner + vec2(300.0, 50.0));
+    simulation.step();
+    let expected = rect(simulation.status().windows.last().unwrap());
+    assert_eq!(expected.size(), window.size() + vec2(300.0, 50.0));
+    simulation.run_for(Duration::from_millis(200));
+
+    drop(simulation);
+    let simulation = continue_session(room.simulation_with_documents());
+    let status = simulation.status();
+    let window = status.windows.last().unwrap();
+    assert_eq!(window.title, "Report");
+    let restored = rect(window);
+    assert!(restored.point().distance(expected.point()) < 0.01);
+    assert!(restored.size().distance(expected.size()) < 0.01);
+}
+
+#[test]
+fn login_shows_the_error() {
+    let room = Room::new("login", "abort");
+    let mut simulation = room.simulation();
+    simulation.type_text("hunter2");
+    simulation.press_key(KeyCode::Enter);
+    simulation.step();
+
+    let status = simulation.status();
+    assert!(!status.is_unlocked);
+    assert_eq!(kinds(&status), [WindowKind::PopUp]);
+    assert_eq!(status.windows[0].title, "Login is disabled!");
+
+    // The close button is in the top-right corner of the popup
+    let center = simulation.screen_size() * 0.5;
+    simulation.click(center + vec2(332.0, -50.0));
+    simulation.step();
+    assert!(simulation.status().windows.is_empty());
+}
+
+#[test]
+fn reset_locks_the_room_again() {
+    let room = Room::new("reset", "abort");
+    let mut simulation = room.simulation();
+    // Skips the hack
+    simulation.press_key(KeyCode::Home);
+    simulation.step();
+    assert!(simulation.status().is_unlocked);
+
+    let modifiers = Modifiers {
+        ctrl: true,
+        shift: true,
+        ..Modifiers::default()
+    };
+    simulation.press_key_with(KeyCode::R, modifiers);
+    simulation.step();
+    let status = simulation.status();
+    assert!(!status.is_unlocked);
+    assert_eq!(status.hack_status, "NoUSB");
+    assert!(status.windows.is_empty());
+}
+
+#[test]
+fn encrypted_document_opens_with_its_key() {
+    let room = Room::new("decrypt", "abort");
+    let mut simulation = room.simulation_with_documents();
+    simulation.control(ControlCommand::OpenDocument("vault".to_string()));
+    simulation.step();
+    assert_eq!(titles(&simulation.status()), ["Vault"]);
+    assert!(simulation.filesystem().is_encrypted("vault"));
+
+    // Links cannot be followed while the page is scrambled
+    let page = on_page(&simulation.status().windows[0]);
+    simulation.click(page);
+    simulation.step();
+    assert_eq!(titles(&simulation.status()), ["Vault"]);
+
+    simulation.type_text("wrong key");
+    simulation.press_key(KeyCode::Enter);
+    simulation.step();
+    assert!(simulation.filesystem().is_encrypted("vault"));
+
+    // Surrounding whitespace is ignored
+    simulation.type_text(" open sesame ");
+    simulation.press_key(KeyCode::Enter);
+    simulation.step();
+    assert!(!simulation.filesystem().is_encrypted("vault"));
+
+    simulation.click(page);
+    simulation.step();
+    assert_eq!(titles(&simulation.status()), ["Appendix"]);
+}
+
+#[test]
+fn redactions_are_revealed_by_payloads_and_the_game_master() {
+    let room = Room::new("reveal", "abort");
+    let mut simulation = room.simulation_with_documents();
+    let meta = DocumentMeta::load(&room.dir.join("report.toml"))
+        .unwrap()
+        .unwrap();
+    let [name, address] = &meta.redactions[..] else {
+        panic!("the report has two redactions");
+    };
+    assert!(!simulation.filesystem().is_revealed(name));
+    assert!(!simulation.filesystem().is_revealed(address));
+
+    simulation.control(ControlCommand::Reveal("unknown".to_string()));
+    simulation.control(ControlCommand::Reveal("address".to_string()));
+    assert!(!simulation.filesystem().is_revealed(name));
+    assert!(simulation.filesystem().is_revealed(address));
+
+    simulation.plug_usb(&room.drive("leak", "leak.hack", &payload::sign(LEAK, KEY.as_bytes())));
+    simulation.step();
+    assert_eq!(titles(&simulation.status()), ["Leaked!"]);
+    assert!(simulation.filesystem().is_revealed(name));
+}
+
+#[test]
+fn links_keep_a_history() {
+    let room = Room::new("links", "abort");
+    let mut simulation = room.simulation_with_documents();
+    simulation.control(ControlCommand::OpenDocument("report".to_string()));
+    simulation.step();
+    let window = simulation.status().windows[0].clone();
+
+    simulation.click(on_page(&window));
+    simulation.step();
+    assert_eq!(titles(&simulation.status()), ["Appendix"]);
+
+    simulation.click(back_button(&window));
+    simulation.step();
+    assert_eq!(titles(&simulation.status()), ["Report"]);
+
+    simulation.click(forward_button(&window));
+    simulation.step();
+    assert_eq!(titles(&simulation.status()), ["Appendix"]);
+
+    // With shift the linked document opens in a window of its own
+    simulation.click_with(
+        on_page(&window),
+        Modifiers {
+            shift: true,
+            ..Modifiers::default()
+        },
+    );
+    simulation.step();
+    assert_eq!(titles(&simulation.status()), ["Appendix", "Report"]);
+
+    // A document that is open already is brought to the front
+    let report = &simulation.status().windows[1];
+    simulation.click(on_page(report));
+    simulation.step();
+    let status = simulation.status();
+    assert_eq!(titles(&status), ["Report", "Appendix"]);
+    assert!(status.windows[1].is_focused);
+}
+
+#[test]
+fn time_lock_opens_after_the_unlock() {
+    let room = Room::new("time_lock", "abort");
+    let mut simulation = room.simulation_with_documents();
+    // The lock does not run before the room is unlocked
+    simulation.run_for(Duration::from_secs(40));
+    simulation.control(ControlCommand::Unlock);
+    simulation.step();
+
+    let status = simulation.status();
+    let terminal = kinds(&status)
+        .iter()
+        .position(|kind| *kind == WindowKind::Terminal)
+        .unwrap();
+    simulation.click(uncovered(&status, terminal));
+    simulation.step();
+    let focused = simulation.status().windows.last().unwrap().kind;
+    assert_eq!(focused, WindowKind::Terminal);
+
+    simulation.type_text("cat /home/agent/intel");
+    simulation.press_key(KeyCode::Enter);
+    simulation.step();
+    assert!(!has_window(&simulation.status(), WindowKind::Document));
+
+    simulation.run_for(Duration::from_secs(30));
+    simulation.type_text("cat /home/agent/intel");
+    simulation.press_key(KeyCode::Enter);
+    simulation.step();
+    let status = simulation.status();
+    assert_eq!(status.windows.last().unwrap().title, "Intel");
+}
+
+#[test]
+fn skipping_the_hack_again_changes_nothing() {
+    let room = Room::new("skip_twice", "abort");
+    let mut simulation = room.simulation();
+    simulation.press_key(KeyCode::Home);
+    simulation.step();
+    let windows = kinds(&simulation.status());
+
+    simulation.press_key(KeyCode::Home);
+    simulation.step();
+    assert_eq!(kinds(&simulation.status()), windows);
+}